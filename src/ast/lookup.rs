@@ -5,6 +5,7 @@ use crate::interpreter::symbols::ComparisonKind;
 use crate::interpreter::symbols::Exps;
 use crate::interpreter::symbols::Logical;
 use crate::interpreter::symbols::UnopKind;
+use crate::span::Span;
 use crate::tokens::Token;
 use crate::interpreter::symbols::OpKind;
 use crate::interpreter::symbols::LogicalKind;
@@ -14,7 +15,7 @@ use super::ASTError;
 
 /// lookup table to convert from token to corresponding infix operator,
 /// returns InvalidOperator error if token doesn't correspond to an OpKind
-pub fn lookup_infix(token: Token) -> Result<OpKind, ASTError> {
+pub fn lookup_infix(token: Token, span: Span) -> Result<OpKind, ASTError> {
     Ok(match token {
         // standard
         Token::Plus => OpKind::Plus,
@@ -33,31 +34,64 @@ pub fn lookup_infix(token: Token) -> Result<OpKind, ASTError> {
         Token::MoreEqual => OpKind::Comparison(Comparison{comparison: ComparisonKind::MoreEqual}),
         Token::NotEqual => OpKind::Comparison(Comparison{comparison: ComparisonKind::NotEqual}),
 
-        other=> return Err(ASTError::InvalidOperator(other))
+        other=> return Err(ASTError::InvalidOperator { found: other, span })
         
     })
 }
 
+/// binding power below every infix operator, used to parse a complete expression
+pub const LOWEST_PRECEDENCE: u8 = 0;
+/// binding power of unary operators, tighter than every infix operator
+pub const UNARY_PRECEDENCE: u8 = 6;
+
+/// lookup table to convert from token to the binding power of the corresponding infix operator,
+/// higher binds tighter. returns None if token doesn't correspond to an OpKind
+pub fn lookup_precedence(token: &Token) -> Option<u8> {
+    Some(match token {
+        // logical
+        Token::Or => 1,
+        Token::And => 2,
+        // comparison
+        Token::Equals
+        | Token::Less
+        | Token::More
+        | Token::LessEqual
+        | Token::MoreEqual
+        | Token::NotEqual => 3,
+        // additive
+        Token::Plus | Token::Minus => 4,
+        // multiplicative
+        Token::Mul | Token::Div | Token::Mod => 5,
+
+        _ => return None,
+    })
+}
+
 /// lookup table to convert from token to corresponding builtin function,
 /// returns InvalidBuiltin error if token doesn't correspond to a BuiltinKind
-pub fn lookup_builtin(token: Token, exps: Exps) -> Result<BuiltInKind, ASTError> {
+pub fn lookup_builtin(token: Token, span: Span, exps: Exps) -> Result<BuiltInKind, ASTError> {
     Ok(match token {
         Token::Sqrt => BuiltInKind::Sqrt(exps),
         Token::Len => BuiltInKind::Len(exps),
         Token::Round => BuiltInKind::Round(exps),
         Token::Input => BuiltInKind::Input(exps),
-        other => return Err(ASTError::InvalidBuiltin(other)) 
+        Token::ToStr => BuiltInKind::ToStr(exps),
+        Token::ToNum => BuiltInKind::ToNum(exps),
+        Token::Substr => BuiltInKind::Substr(exps),
+        Token::Split => BuiltInKind::Split(exps),
+        Token::Array => BuiltInKind::Array(exps),
+        other => return Err(ASTError::InvalidBuiltin { found: other, span })
     })
 }
 
 
 /// lookup table to convert from token to corresponding unary operator,
 /// returns InvalidOperator error if token doesn't correspond to a UnopKind
-pub fn lookup_unop(token: Token) -> Result<UnopKind, ASTError> {
+pub fn lookup_unop(token: Token, span: Span) -> Result<UnopKind, ASTError> {
     Ok(match token {
         Token::Minus => UnopKind::Neg,
         Token::Not => UnopKind::Not,
         // todo: seperate invalidunaryoperator error?
-        other => return Err(ASTError::InvalidOperator(other))
+        other => return Err(ASTError::InvalidOperator { found: other, span })
     })
 }
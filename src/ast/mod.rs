@@ -1,5 +1,8 @@
+use std::fmt::Display;
+
 use crate::interpreter::symbols::*;
 use crate::scanner::{Scanner, ScannerError};
+use crate::span::Span;
 use crate::tokens::{Token, TokenDiscriminants};
 
 use self::lookup::lookup_unop;
@@ -7,6 +10,7 @@ use self::lookup::lookup_unop;
 mod lookup;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod test;
 
 const DISCRIMINANT_ERROR: &str = "Enum variant did not match discriminant";
@@ -14,9 +18,31 @@ const DISCRIMINANT_ERROR: &str = "Enum variant did not match discriminant";
 #[derive(Debug)]
 pub enum ASTError {
     ScannerError(ScannerError),
-    UnexpectedToken(Token),
-    InvalidOperator(Token),
-    InvalidBuiltin(Token),
+    UnexpectedToken {
+        found: Token,
+        expected: Vec<Expected>,
+        span: Span,
+    },
+    InvalidOperator { found: Token, span: Span },
+    InvalidBuiltin { found: Token, span: Span },
+    // break or continue that isn't inside a loop
+    OutsideLoop { found: Token, span: Span },
+    // a single number in brackets, which used to create an array of that many zeros
+    ArraySizeLiteral { size: f64, span: Span },
+}
+
+impl ASTError {
+    // the source location this error was raised at
+    pub fn span(&self) -> Span {
+        match self {
+            ASTError::ScannerError(err) => err.span(),
+            ASTError::UnexpectedToken { span, .. }
+            | ASTError::InvalidOperator { span, .. }
+            | ASTError::InvalidBuiltin { span, .. }
+            | ASTError::OutsideLoop { span, .. }
+            | ASTError::ArraySizeLiteral { span, .. } => *span,
+        }
+    }
 }
 
 impl From<ScannerError> for ASTError {
@@ -25,414 +51,761 @@ impl From<ScannerError> for ASTError {
     }
 }
 
+// Expected is something the parser would have accepted where it found an unexpected token
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expected {
+    Token(TokenDiscriminants),
+    Operator,
+    Expression,
+}
+
+// Display for Expected, used in error messages
+impl Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expected::Token(TokenDiscriminants::Name) => write!(f, "name"),
+            Expected::Token(TokenDiscriminants::Num) => write!(f, "number"),
+            Expected::Token(TokenDiscriminants::Str) => write!(f, "string"),
+            Expected::Token(TokenDiscriminants::Eof) => write!(f, "end of file"),
+            Expected::Token(token) => write!(f, "`{}`", token),
+            Expected::Operator => write!(f, "operator"),
+            Expected::Expression => write!(f, "expression"),
+        }
+    }
+}
+
+// Generates AST for program, stopping at the first syntax error
 pub fn generate_ast(scanner: &mut Scanner) -> Result<Program, ASTError> {
-    let mut defs = Vec::new();
-    while !scanner.is_eof() {
-        let def = generate_def(scanner)?;
-        defs.push(def);
+    let (program, mut errors) = generate_ast_recovering(scanner);
+    if errors.is_empty() {
+        return Ok(program);
     }
+    Err(errors.remove(0))
+}
+
+// Generates AST for program, recovering from syntax errors to find as many as possible.
+// returns every def that could be parsed along with every error found, in source order.
+pub fn generate_ast_recovering(scanner: &mut Scanner) -> (Program, Vec<ASTError>) {
+    let mut parser = Parser::new(scanner);
+    let program = parser.generate_program();
+    (program, parser.errors)
+}
 
-    Ok(Program { defs })
+// Generates AST for a sequence of defs and statements, as entered in an interactive session.
+// recovers from syntax errors like generate_ast_recovering.
+pub fn generate_items_recovering(scanner: &mut Scanner) -> (Vec<Item>, Vec<ASTError>) {
+    let mut parser = Parser::new(scanner);
+    let items = parser.generate_items();
+    (items, parser.errors)
 }
 
-fn generate_def(scanner: &mut Scanner) -> Result<Def, ASTError> {
-    // consume def
-    consume_token(scanner, TokenDiscriminants::Def)?;
+// Parser generates AST from the tokens of a Scanner
+struct Parser<'a> {
+    scanner: &'a mut Scanner,
+    // everything that was checked for and not found at the next token,
+    // these are reported as expected if the next token turns out to be an error.
+    expected: Vec<Expected>,
+    // errors recovered from so far
+    errors: Vec<ASTError>,
+    // how many loops the statement being parsed is inside of
+    loop_depth: usize,
+}
 
-    // consume name
-    let name = match consume_token(scanner, TokenDiscriminants::Name)? {
-        Token::Name(value) => value,
-        _ => panic!("{}", DISCRIMINANT_ERROR),
-    };
+impl<'a> Parser<'a> {
+    fn new(scanner: &'a mut Scanner) -> Parser<'a> {
+        Parser {
+            scanner,
+            expected: Vec::new(),
+            errors: Vec::new(),
+            loop_depth: 0,
+        }
+    }
+
+    // Generates AST for program
+    // on an error outside any statement, skips ahead to the next def or const
+    fn generate_program(&mut self) -> Program {
+        let mut defs = Vec::new();
+        let mut consts = Vec::new();
+        while !self.check(TokenDiscriminants::Eof) {
+            let result = if self.check(TokenDiscriminants::Const) {
+                self.generate_const().map(|c| consts.push(c))
+            } else {
+                self.generate_def().map(|def| defs.push(def))
+            };
+            if let Err(err) = result {
+                self.errors.push(err);
+                self.sync_def();
+            }
+        }
 
-    // consume (
-    consume_token(scanner, TokenDiscriminants::LParen)?;
+        Program { defs, consts }
+    }
 
-    // consume args
-    let args = generate_args(scanner)?;
+    // Generates AST for a sequence of items
+    // on an error, skips ahead to the end of the statement or def it was found in
+    fn generate_items(&mut self) -> Vec<Item> {
+        let mut items = Vec::new();
+        while !self.check(TokenDiscriminants::Eof) {
+            if variant_equal(&self.scanner.peek_next(), TokenDiscriminants::Def) {
+                match self.generate_def() {
+                    Ok(def) => items.push(Item::Def(def)),
+                    Err(err) => {
+                        self.errors.push(err);
+                        self.sync_def();
+                    }
+                }
+                continue;
+            }
+            if variant_equal(&self.scanner.peek_next(), TokenDiscriminants::Const) {
+                match self.generate_const() {
+                    Ok(c) => items.push(Item::Const(c)),
+                    Err(err) => {
+                        self.errors.push(err);
+                        self.sync_statement();
+                    }
+                }
+                continue;
+            }
 
-    // consume )
-    consume_token(scanner, TokenDiscriminants::RParen)?;
+            match self.generate_statement() {
+                Ok(statement) => items.push(Item::Statement(statement)),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.sync_statement();
+                    // there is no surrounding block for a } to close here
+                    if variant_equal(&self.scanner.peek_next(), TokenDiscriminants::RCurly) {
+                        self.skip_token();
+                    }
+                }
+            }
+        }
 
-    let block = generate_block(scanner)?;
+        items
+    }
 
-    Ok(Def { name, args, block })
-}
+    fn generate_const(&mut self) -> Result<Const, ASTError> {
+        let start = self.scanner.peek_span();
+        // consume const
+        self.consume_token(TokenDiscriminants::Const)?;
 
-// Generates AST for function def args
-fn generate_args(scanner: &mut Scanner) -> Result<Args, ASTError> {
-    let mut names = Vec::new();
-    while !variant_equal(&scanner.peek_next(), TokenDiscriminants::RParen) {
         // consume name
-        let arg = match consume_token(scanner, TokenDiscriminants::Name)? {
+        let name = match self.consume_token(TokenDiscriminants::Name)? {
             Token::Name(value) => value,
             _ => panic!("{}", DISCRIMINANT_ERROR),
         };
-        names.push(arg);
-
-        let next = scanner.peek_next();
-        if variant_equal(&next, TokenDiscriminants::Comma) {
-            // consume ,
-            consume_token(scanner, TokenDiscriminants::Comma)?;
-        } else if !variant_equal(&next, TokenDiscriminants::RParen) {
-            // if we see no more commas after an arg, we must be at the last arg
-            // therefore, ) must be next (however we leave consuming it to the caller)
-            return Err(ASTError::UnexpectedToken(next));
-        }
+
+        // consume :=
+        self.consume_token(TokenDiscriminants::Assign)?;
+        let exp = self.generate_exp()?;
+        // consume ;
+        self.consume_token(TokenDiscriminants::SColon)?;
+
+        Ok(Const {
+            name,
+            exp,
+            span: start.to(self.scanner.last_span()),
+        })
     }
-    Ok(Args { names })
-}
 
-// Generates AST for a block
-fn generate_block(scanner: &mut Scanner) -> Result<Block, ASTError> {
-    // consume {
-    consume_token(scanner, TokenDiscriminants::LCurly)?;
+    fn generate_def(&mut self) -> Result<Def, ASTError> {
+        let start = self.scanner.peek_span();
+        // consume def
+        self.consume_token(TokenDiscriminants::Def)?;
 
-    let mut statements = Vec::new();
-    // keep consuming statements until we reach the end of the block
-    while !variant_equal(&scanner.peek_next(), TokenDiscriminants::RCurly) {
-        statements.push(generate_statement(scanner)?);
-    }
+        // consume name
+        let name = match self.consume_token(TokenDiscriminants::Name)? {
+            Token::Name(value) => value,
+            _ => panic!("{}", DISCRIMINANT_ERROR),
+        };
 
-    // consume }
-    consume_token(scanner, TokenDiscriminants::RCurly)?;
+        // consume (
+        self.consume_token(TokenDiscriminants::LParen)?;
 
-    Ok(Block { statements })
-}
+        // consume args
+        let args = self.generate_args()?;
 
-// Generates AST for statment
-fn generate_statement(scanner: &mut Scanner) -> Result<Statement, ASTError> {
-    let statement = match scanner.peek_next() {
-        Token::Return => {
-            // consume return
-            consume_token(scanner, TokenDiscriminants::Return)?;
-            // consume exp
-            let exp = generate_exp(scanner)?;
-            // consume ;
-            consume_token(scanner, TokenDiscriminants::SColon)?;
-            StatementKind::Return(exp)
-        }
-        Token::If | Token::While => StatementKind::Nest(generate_nest(scanner)?),
-        // here there is some ambiguity, if name is next, this statement
-        // could either be: a. an assigment, or b. an exp.
-        // we consume the token to look ahead to check for the assigment operator.
-        Token::Name(name) => {
+        // consume )
+        self.consume_token(TokenDiscriminants::RParen)?;
+
+        let block = self.generate_block()?;
+
+        Ok(Def {
+            name,
+            args,
+            block,
+            span: start.to(self.scanner.last_span()),
+        })
+    }
+
+    // Generates AST for function def args
+    fn generate_args(&mut self) -> Result<Args, ASTError> {
+        let mut names = Vec::new();
+        let mut spans = Vec::new();
+        while !self.check(TokenDiscriminants::RParen) {
             // consume name
-            consume_token(scanner, TokenDiscriminants::Name)?;
-            match scanner.peek_next() {
-                // variable assignment
-                Token::Assign => {
-                    // consume :=
-                    consume_token(scanner, TokenDiscriminants::Assign)?;
-                    let exp = generate_exp(scanner)?;
-                    // consume ;
-                    consume_token(scanner, TokenDiscriminants::SColon)?;
-                    StatementKind::Assign { name, exp }
-                }
-                // arrays
-                Token::LBracket => {
-                    // consume [
-                    consume_token(scanner, TokenDiscriminants::LBracket)?;
-                    // consume index exp
-                    let index_exp = generate_exp(scanner)?;
-                    // consume ]
-                    consume_token(scanner, TokenDiscriminants::RBracket)?;
-
-                    // ArrayAssign
-                    if variant_equal(&scanner.peek_next(), TokenDiscriminants::Assign) {
-                        // consume :=
-                        consume_token(scanner, TokenDiscriminants::Assign)?;
-                        // consume value exp
-                        let value = generate_exp(scanner)?;
-                        // consume ;
-                        consume_token(scanner, TokenDiscriminants::SColon)?;
-                        StatementKind::ArrayAssign {
-                            name,
-                            index_exp,
-                            value,
-                        }
-                    // Array Access
-                    } else {
-                        // consume array access
-                        let preexp = Exp {
-                            exp: Box::new(ExpKind::ArrayAccess {
-                                name,
-                                index: index_exp,
-                            }),
-                            src_ln: scanner.src_line,
-                        };
-                        // consume rest of expression
-                        let exp = generate_exp_preexp(scanner, preexp)?;
-                        // consume ;
-                        consume_token(scanner, TokenDiscriminants::SColon)?;
-                        StatementKind::Exp(exp)
-                    }
-                }
-                // otherwise, just an exp starting with a name (e.i usage or fn call)
-                _ => {
-                    let exp = generate_exp_name(scanner, name)?;
-                    // consume ;
-                    consume_token(scanner, TokenDiscriminants::SColon)?;
-                    StatementKind::Exp(exp)
-                }
+            let arg = match self.consume_token(TokenDiscriminants::Name)? {
+                Token::Name(value) => value,
+                _ => panic!("{}", DISCRIMINANT_ERROR),
+            };
+            names.push(arg);
+            spans.push(self.scanner.last_span());
+
+            if self.check(TokenDiscriminants::Comma) {
+                // consume ,
+                self.consume_token(TokenDiscriminants::Comma)?;
+            } else if !self.check(TokenDiscriminants::RParen) {
+                // if we see no more commas after an arg, we must be at the last arg
+                // therefore, ) must be next (however we leave consuming it to the caller)
+                return Err(self.unexpected());
             }
         }
-        // try to parse an exp
-        _ => {
-            let exp = generate_exp(scanner)?;
-            consume_token(scanner, TokenDiscriminants::SColon)?;
-            StatementKind::Exp(exp)
+        Ok(Args { names, spans })
+    }
+
+    // Generates AST for a block
+    // on an error inside a statement, skips ahead to the end of that statement and continues
+    fn generate_block(&mut self) -> Result<Block, ASTError> {
+        let start = self.scanner.peek_span();
+        // consume {
+        self.consume_token(TokenDiscriminants::LCurly)?;
+
+        let mut statements = Vec::new();
+        // keep consuming statements until we reach the end of the block.
+        // a def can't be inside a block, so one here means this block is missing its }
+        while !self.check(TokenDiscriminants::RCurly)
+            && !matches!(self.scanner.peek_next(), Token::Eof | Token::Def)
+        {
+            match self.generate_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.sync_statement();
+                }
+            }
         }
-    };
 
-    Ok(Statement { statement })
-}
+        // consume }
+        self.consume_token(TokenDiscriminants::RCurly)?;
 
-// Generates AST for exp
-fn generate_exp(scanner: &mut Scanner) -> Result<Exp, ASTError> {
-    Ok(match scanner.peek_next() {
-        // let all name-first expressions get handled by special case
-        Token::Name(name) => {
-            // consume name
-            consume_token(scanner, TokenDiscriminants::Name)?;
-            return generate_exp_name(scanner, name);
+        Ok(Block {
+            statements,
+            span: start.to(self.scanner.last_span()),
+        })
+    }
+
+    // Generates AST for a statement without its ;, e.i. an assignment or an exp.
+    // these also make up the header of a for loop
+    fn generate_simple_statement(&mut self) -> Result<StatementKind, ASTError> {
+        let name = match self.scanner.peek_next() {
+            Token::Name(name) => name,
+            Token::Let => {
+                // consume let
+                self.consume_token(TokenDiscriminants::Let)?;
+                // consume name
+                let name = match self.consume_token(TokenDiscriminants::Name)? {
+                    Token::Name(name) => name,
+                    _ => panic!("{}", DISCRIMINANT_ERROR),
+                };
+                // consume :=
+                self.consume_token(TokenDiscriminants::Assign)?;
+                let exp = self.generate_exp()?;
+                return Ok(StatementKind::Let { name, exp });
+            }
+            // try to parse an exp
+            _ => return Ok(StatementKind::Exp(self.generate_exp()?)),
+        };
+
+        // here there is some ambiguity, if name is next, this statement
+        // could either be: a. an assigment, or b. an exp.
+        // we consume the token to look ahead to check for the assigment operator.
+        let start = self.scanner.peek_span();
+        // consume name
+        self.consume_token(TokenDiscriminants::Name)?;
+        if let Token::Assign = self.scanner.peek_next() {
+            // variable assignment
+            // consume :=
+            self.consume_token(TokenDiscriminants::Assign)?;
+            let exp = self.generate_exp()?;
+            return Ok(StatementKind::Assign { name, exp });
         }
-        // builtins
-        Token::Sqrt | Token::Len | Token::Round | Token::Input => {
-            // consume builtin
-            let builtin = scanner.next_token()?;
-            let preexp = Exp {
-                exp: Box::new(ExpKind::BuiltIn(BuiltIn {
-                    builtin: lookup::lookup_builtin(builtin, generate_exps(scanner)?)?,
-                })),
-                src_ln: scanner.src_line,
-            };
-            return generate_exp_preexp(scanner, preexp);
+
+        // otherwise an exp starting with a name (e.i usage, fn call or array access),
+        // which is assigned to if it turns out to be an lvalue
+        let preexp = self.generate_exp_name(name, start)?;
+        let preexp = self.generate_postfix(preexp, start)?;
+        match lvalue(&preexp) {
+            Some((name, path)) if self.check(TokenDiscriminants::Assign) => {
+                // consume :=
+                self.consume_token(TokenDiscriminants::Assign)?;
+                // consume value exp
+                let value = self.generate_exp()?;
+                Ok(StatementKind::ArrayAssign { name, path, value })
+            }
+            _ => Ok(StatementKind::Exp(self.generate_exp_preexp(preexp)?)),
         }
-        // num and infix cases
-        Token::Num(value) => {
-            // consume num
-            consume_token(scanner, TokenDiscriminants::Num)?;
-            match scanner.peek_next() {
-                // just a number followed by ; or , or )
-                // todo: i think if i include lcurly here, i can ditch parens in nest conditional
-                Token::SColon | Token::Comma | Token::RParen | Token::RBracket => {
-                    return Ok(Exp {
-                        exp: Box::new(ExpKind::Num(value)),
-                        src_ln: scanner.src_line,
-                    });
+    }
+
+    // Generates AST for statment
+    fn generate_statement(&mut self) -> Result<Statement, ASTError> {
+        let start = self.scanner.peek_span();
+        let statement = match self.scanner.peek_next() {
+            Token::Return => {
+                // consume return
+                self.consume_token(TokenDiscriminants::Return)?;
+                // consume exp
+                let exp = self.generate_exp()?;
+                // consume ;
+                self.consume_token(TokenDiscriminants::SColon)?;
+                StatementKind::Return(exp)
+            }
+            Token::If | Token::While | Token::For => StatementKind::Nest(self.generate_nest()?),
+            Token::Break | Token::Continue => {
+                // consume break or continue
+                let token = self.next_token()?;
+                if self.loop_depth == 0 {
+                    return Err(ASTError::OutsideLoop { found: token, span: start });
                 }
-                // Infix operators
-                _ => {
-                    // generate infix: num op exp
-                    // operator is next, and is consumed by next_token, leaving generate_exp to get exp
-                    generate_infix(
-                        ExpKind::Num(value),
-                        lookup::lookup_infix(scanner.next_token()?)?,
-                        generate_exp(scanner)?,
-                    )?
+                // consume ;
+                self.consume_token(TokenDiscriminants::SColon)?;
+                match token {
+                    Token::Break => StatementKind::Break,
+                    _ => StatementKind::Continue,
                 }
             }
-        }
-        // array initialization
-        Token::LBracket => {
-            // consume [
-            consume_token(scanner, TokenDiscriminants::LBracket)?;
-            // consume size exp
-            let exp = generate_exp(scanner)?;
+            _ => {
+                let statement = self.generate_simple_statement()?;
+                // consume ;
+                self.consume_token(TokenDiscriminants::SColon)?;
+                statement
+            }
+        };
 
-            // consume ]
-            consume_token(scanner, TokenDiscriminants::RBracket)?;
+        Ok(Statement {
+            statement,
+            span: start.to(self.scanner.last_span()),
+        })
+    }
 
-            return Ok(Exp {
-                exp: Box::new(ExpKind::ArrayInit { size: exp }),
-                src_ln: scanner.src_line,
-            });
-        }
-        // parenthesized exp
-        Token::LParen => {
-            // consume (
-            consume_token(scanner, TokenDiscriminants::LParen)?;
-            // consume exp
-            let exp = generate_exp(scanner)?;
-            // consume )
-            consume_token(scanner, TokenDiscriminants::RParen)?;
-
-            // checks to see if expression in parens is continued
-            return generate_exp_preexp(scanner, exp);
-        }
+    // Generates AST for exp
+    fn generate_exp(&mut self) -> Result<Exp, ASTError> {
+        self.generate_exp_prec(lookup::LOWEST_PRECEDENCE)
+    }
+
+    // Generates AST for an exp whose infix operators all bind at least as tightly as min_prec.
+    // This is the core of the precedence climbing (Pratt) parser: a prefix (operand) is parsed first,
+    // then infix operators are folded into it for as long as they bind tightly enough.
+    fn generate_exp_prec(&mut self, min_prec: u8) -> Result<Exp, ASTError> {
+        let prefix = self.generate_prefix()?;
+        self.generate_infix(prefix, min_prec)
+    }
 
-        // unary operator expressions
-        Token::Minus | Token::Not => {
-            let unop = scanner.next_token()?;
-            let exp = generate_exp(scanner)?;
-            return Ok(Exp {
-                exp: Box::new(ExpKind::Unary(
+    // Generates AST for the operand of an exp, e.i. anything but an infix expression
+    fn generate_prefix(&mut self) -> Result<Exp, ASTError> {
+        let start = self.scanner.peek_span();
+        let operand = self.generate_operand()?;
+        self.generate_postfix(operand, start)
+    }
+
+    // Generates AST for an operand without any indexing that follows it
+    fn generate_operand(&mut self) -> Result<Exp, ASTError> {
+        let start = self.scanner.peek_span();
+        let exp = match self.scanner.peek_next() {
+            // let all name-first expressions get handled by special case
+            Token::Name(name) => {
+                // consume name
+                self.consume_token(TokenDiscriminants::Name)?;
+                return self.generate_exp_name(name, start);
+            }
+            // builtins
+            Token::Sqrt
+            | Token::Len
+            | Token::Round
+            | Token::Input
+            | Token::ToStr
+            | Token::ToNum
+            | Token::Substr
+            | Token::Split
+            | Token::Array => {
+                // consume builtin
+                let builtin = self.next_token()?;
+                ExpKind::BuiltIn(BuiltIn {
+                    builtin: lookup::lookup_builtin(builtin, start, self.generate_exps()?)?,
+                })
+            }
+            // num
+            Token::Num(value) => {
+                // consume num
+                self.consume_token(TokenDiscriminants::Num)?;
+                ExpKind::Num(value)
+            }
+            // string
+            Token::Str(value) => {
+                // consume string
+                self.consume_token(TokenDiscriminants::Str)?;
+                ExpKind::Str(value)
+            }
+            // boolean
+            Token::True | Token::False => {
+                // consume true or false
+                let token = self.next_token()?;
+                ExpKind::Bool(token == Token::True)
+            }
+            // array literal
+            Token::LBracket => {
+                // consume [
+                self.consume_token(TokenDiscriminants::LBracket)?;
+                let first = self.scanner.peek_span();
+                let elements = self.generate_list_rest(TokenDiscriminants::RBracket)?;
+                // [n] used to create an array of n zeros, which array(n) does now.
+                // rather than quietly becoming an array holding n, it is an error
+                if let [element] = &elements.exps[..] {
+                    if let ExpKind::Num(size) = *element.exp {
+                        if element.span == first {
+                            let span = start.to(self.scanner.last_span());
+                            return Err(ASTError::ArraySizeLiteral { size, span });
+                        }
+                    }
+                }
+                ExpKind::Array(elements)
+            }
+            // parenthesized exp
+            Token::LParen => {
+                // consume (
+                self.consume_token(TokenDiscriminants::LParen)?;
+                // consume exp
+                let exp = self.generate_exp()?;
+                // consume )
+                self.consume_token(TokenDiscriminants::RParen)?;
+                return Ok(exp);
+            }
+            // unary operator expressions, these bind tighter than any infix operator
+            Token::Minus | Token::Not => {
+                let unop = self.next_token()?;
+                let exp = self.generate_exp_prec(lookup::UNARY_PRECEDENCE)?;
+                ExpKind::Unary(
                     Unop {
-                        unop: lookup_unop(unop)?,
+                        unop: lookup_unop(unop, start)?,
                     },
                     exp,
-                )),
-                src_ln: scanner.src_line,
-            });
-        }
-        // illegal
-        other => {
-            dbg!();
-            return Err(ASTError::UnexpectedToken(other));
-        }
-    })
-}
+                )
+            }
+            // illegal
+            _ => {
+                self.expected.push(Expected::Expression);
+                return Err(self.unexpected());
+            }
+        };
 
-// either returns just expression, or full expression with next
-// infix operator.
-// 1+1 => Infix(1, +, 1)
-// (1) + 1 => Infix((1), +, 1)
-// this is to help with cases where a statment contains a paren exp followed by an operator.
-fn generate_exp_preexp(scanner: &mut Scanner, preexp: Exp) -> Result<Exp, ASTError> {
-    match scanner.peek_next() {
-        // lone expression
-        Token::SColon | Token::LCurly | Token::RParen | Token::RBracket => Ok(preexp),
-        // else must be infix
-        _ => Ok(Exp {
-            // consumes infix
-            exp: Box::new(ExpKind::Infix(
-                preexp,
-                Op {
-                    op: lookup::lookup_infix(scanner.next_token()?)?,
-                },
-                generate_exp(scanner)?,
-            )),
-            src_ln: scanner.src_line,
-        }),
+        Ok(Exp {
+            exp: Box::new(exp),
+            span: start.to(self.scanner.last_span()),
+        })
     }
-}
 
-// special case of generate exp, beggining with a passed name
-// that we had to consume in generate_statment or generate_exp to look ahead
-// exp ::= name | exp op exp | name "(" exps ")"
-fn generate_exp_name(scanner: &mut Scanner, name: String) -> Result<Exp, ASTError> {
-    let exp = match scanner.peek_next() {
-        // name on it's own
-        Token::SColon | Token::Comma | Token::RParen | Token::RBracket => ExpKind::Name(name),
-        // name followed by parens (function call)
-        Token::LParen => {
-            // consume (
-            consume_token(scanner, TokenDiscriminants::LParen)?;
-            // compute function args
-            let mut exps = Vec::new();
-            while !variant_equal(&scanner.peek_next(), TokenDiscriminants::RParen) {
-                let param = generate_exp(scanner)?;
-                exps.push(param);
-                if variant_equal(&scanner.peek_next(), TokenDiscriminants::Comma) {
-                    consume_token(scanner, TokenDiscriminants::Comma)?;
+    // Folds any infix operators following lhs into it, as long as they bind at least as tightly as min_prec.
+    // The rhs of each operator only takes operators that bind strictly tighter, making operators
+    // of equal precedence left-associative.
+    // 2 * 3 + 4 => Infix(Infix(2, *, 3), +, 4)
+    // 10 - 2 - 3 => Infix(Infix(10, -, 2), -, 3)
+    fn generate_infix(&mut self, lhs: Exp, min_prec: u8) -> Result<Exp, ASTError> {
+        let mut lhs = lhs;
+        loop {
+            let prec = match lookup::lookup_precedence(&self.scanner.peek_next()) {
+                Some(prec) if prec >= min_prec => prec,
+                Some(_) => break,
+                None => {
+                    // an operator could have continued the expression
+                    self.expected.push(Expected::Operator);
+                    break;
                 }
-            }
+            };
 
-            // consume )
-            consume_token(scanner, TokenDiscriminants::RParen)?;
-            ExpKind::Call(name, Exps { exps })
+            // consume operator
+            let op_span = self.scanner.peek_span();
+            let op = lookup::lookup_infix(self.next_token()?, op_span)?;
+            let rhs = self.generate_exp_prec(prec + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Exp {
+                exp: Box::new(ExpKind::Infix(lhs, Op { op }, rhs)),
+                span,
+            };
         }
-        Token::LBracket => {
+
+        Ok(lhs)
+    }
+
+    // Folds any indexing following operand (found at start) into it, indexing binds tighter than any operator.
+    // m[1][2] => ArrayAccess(ArrayAccess(m, 1), 2)
+    fn generate_postfix(&mut self, operand: Exp, start: Span) -> Result<Exp, ASTError> {
+        let mut exp = operand;
+        while let Token::LBracket = self.scanner.peek_next() {
             // consume [
-            consume_token(scanner, TokenDiscriminants::LBracket)?;
-            // consume exp
-            let index = generate_exp(scanner)?;
+            self.consume_token(TokenDiscriminants::LBracket)?;
+            // consume index exp
+            let index = self.generate_exp()?;
             // consume ]
-            consume_token(scanner, TokenDiscriminants::RBracket)?;
-            ExpKind::ArrayAccess { name, index }
-        }
-        // infix starting with name
-        _ => {
-            // generate infix: name op exp
-            // operator is next, and is consumed by next_token, leaving generate_exp to get exp
-            return generate_infix(
-                ExpKind::Name(name),
-                lookup::lookup_infix(scanner.next_token()?)?,
-                generate_exp(scanner)?,
-            );
+            self.consume_token(TokenDiscriminants::RBracket)?;
+            exp = Exp {
+                exp: Box::new(ExpKind::ArrayAccess { array: exp, index }),
+                span: start.to(self.scanner.last_span()),
+            };
         }
-    };
+        Ok(exp)
+    }
 
-    Ok(Exp { exp: Box::new(exp), src_ln: scanner.src_line })
-}
+    // either returns just expression, or full expression with any following
+    // infix operators.
+    // 1+1 => Infix(1, +, 1)
+    // (1) + 1 => Infix((1), +, 1)
+    // this is to help with cases where a statment contains an exp that had to be consumed to look ahead.
+    fn generate_exp_preexp(&mut self, preexp: Exp) -> Result<Exp, ASTError> {
+        self.generate_infix(preexp, lookup::LOWEST_PRECEDENCE)
+    }
 
-fn generate_exps(scanner: &mut Scanner) -> Result<Exps, ASTError> {
-    let mut exps = Vec::new();
-    // consume (
-    consume_token(scanner, TokenDiscriminants::LParen)?;
-
-    // consume args
-    while !variant_equal(&scanner.peek_next(), TokenDiscriminants::RParen) {
-        exps.push(generate_exp(scanner)?);
-        // consume a comma if not the last arg
-        if !variant_equal(&scanner.peek_next(), TokenDiscriminants::RParen) {
-            consume_token(scanner, TokenDiscriminants::Comma)?;
+    // special case of generate exp, beggining with a passed name (found at start)
+    // that we had to consume in generate_statment or generate_exp to look ahead
+    // exp ::= name | name "(" exps ")"
+    fn generate_exp_name(&mut self, name: String, start: Span) -> Result<Exp, ASTError> {
+        let exp = match self.scanner.peek_next() {
+            // name followed by parens (function call)
+            Token::LParen => ExpKind::Call(name, self.generate_exps()?),
+            // name on it's own
+            _ => ExpKind::Name(name),
+        };
+
+        Ok(Exp {
+            exp: Box::new(exp),
+            span: start.to(self.scanner.last_span()),
+        })
+    }
+
+    // Generates AST for a parenthesized, comma seperated list of exps
+    fn generate_exps(&mut self) -> Result<Exps, ASTError> {
+        self.generate_list(TokenDiscriminants::LParen, TokenDiscriminants::RParen)
+    }
+
+    // Generates AST for a comma seperated list of exps between open and close
+    fn generate_list(&mut self, open: TokenDiscriminants, close: TokenDiscriminants) -> Result<Exps, ASTError> {
+        // consume open
+        self.consume_token(open)?;
+        self.generate_list_rest(close)
+    }
+
+    // Generates AST for the rest of a list after its open token, up to and including close
+    fn generate_list_rest(&mut self, close: TokenDiscriminants) -> Result<Exps, ASTError> {
+        let mut exps = Vec::new();
+
+        // consume exps
+        while !self.check(close) {
+            exps.push(self.generate_exp()?);
+            // consume a comma if not the last exp
+            if !self.check(close) {
+                self.consume_token(TokenDiscriminants::Comma)?;
+            }
         }
+        // consume close
+        self.consume_token(close)?;
+
+        Ok(Exps { exps })
     }
-    // consume )
-    consume_token(scanner, TokenDiscriminants::RParen)?;
 
-    Ok(Exps { exps })
-}
+    // generate AST for Nest: If, If/Else, If/Else If chains, While, For and For/In
+    fn generate_nest(&mut self) -> Result<Nest, ASTError> {
+        let start = self.scanner.peek_span();
+        // consume If, While or For
+        let nest = match self.scanner.peek_next() {
+            Token::If => {
+                self.consume_token(TokenDiscriminants::If)?;
+                // consume cond
+                let cond = self.generate_exp()?;
+                // consume then
+                let then = self.generate_block()?;
+
+                // consume any else ifs, then a final else
+                let mut branches = vec![(cond, then)];
+                let mut else_ = None;
+                while self.check(TokenDiscriminants::Else) {
+                    // consume else
+                    self.consume_token(TokenDiscriminants::Else)?;
+                    if self.check(TokenDiscriminants::If) {
+                        // consume if
+                        self.consume_token(TokenDiscriminants::If)?;
+                        let cond = self.generate_exp()?;
+                        let block = self.generate_block()?;
+                        branches.push((cond, block));
+                    } else {
+                        else_ = Some(self.generate_block()?);
+                        break;
+                    }
+                }
 
-// generate AST for Nest: If, If/Else, and While
-fn generate_nest(scanner: &mut Scanner) -> Result<Nest, ASTError> {
-    // consume If or While
-    let next = scanner.next_token()?;
-    let nest = match next {
-        Token::If => {
-            // consume cond
-            let cond = generate_exp(scanner)?;
-            // consume then
-            let then = generate_block(scanner)?;
-            // if the nest statement is an else
-            if variant_equal(&scanner.peek_next(), TokenDiscriminants::Else) {
-                // consume else
-                consume_token(scanner, TokenDiscriminants::Else)?;
-                // consume else_ block
-                let else_ = generate_block(scanner)?;
-                NestKind::IfElse { cond, then, else_ }
-            // otherwise, just an If
-            } else {
-                NestKind::If { cond, then }
+                // a single branch is a plain If or If/Else
+                if branches.len() > 1 {
+                    NestKind::IfChain { branches, else_ }
+                } else {
+                    let (cond, then) = branches.pop().expect("the if is the first branch");
+                    match else_ {
+                        Some(else_) => NestKind::IfElse { cond, then, else_ },
+                        None => NestKind::If { cond, then },
+                    }
+                }
+            }
+            Token::While => {
+                self.consume_token(TokenDiscriminants::While)?;
+                // consume cond
+                let cond = self.generate_exp()?;
+                // consume block
+                let block = self.generate_loop_block()?;
+                NestKind::While { cond, block }
+            }
+            Token::For => {
+                self.consume_token(TokenDiscriminants::For)?;
+                // a for in loop starts with the name of its variable, a counting loop has parens
+                if let Token::Name(name) = self.scanner.peek_next() {
+                    // consume name
+                    self.consume_token(TokenDiscriminants::Name)?;
+                    // consume in
+                    self.consume_token(TokenDiscriminants::In)?;
+                    // consume the exp looped over
+                    let iterable = self.generate_exp()?;
+                    // consume block
+                    let block = self.generate_loop_block()?;
+                    NestKind::ForIn { name, iterable, block }
+                } else {
+                    // consume (
+                    self.consume_token(TokenDiscriminants::LParen)?;
+                    // consume init, cond and step
+                    let init = self.generate_header_statement()?;
+                    self.consume_token(TokenDiscriminants::SColon)?;
+                    let cond = self.generate_exp()?;
+                    self.consume_token(TokenDiscriminants::SColon)?;
+                    let step = self.generate_header_statement()?;
+                    // consume )
+                    self.consume_token(TokenDiscriminants::RParen)?;
+                    // consume block
+                    let block = self.generate_loop_block()?;
+                    NestKind::For {
+                        init: Box::new(init),
+                        cond,
+                        step: Box::new(step),
+                        block,
+                    }
+                }
+            }
+            _ => {
+                self.expected.push(Expected::Token(TokenDiscriminants::If));
+                self.expected.push(Expected::Token(TokenDiscriminants::While));
+                self.expected.push(Expected::Token(TokenDiscriminants::For));
+                return Err(self.unexpected());
+            }
+        };
+
+        Ok(Nest {
+            nest,
+            span: start.to(self.scanner.last_span()),
+        })
+    }
+
+    // Generates AST for the init or step statement in the header of a for loop
+    fn generate_header_statement(&mut self) -> Result<Statement, ASTError> {
+        let start = self.scanner.peek_span();
+        let statement = self.generate_simple_statement()?;
+        Ok(Statement {
+            statement,
+            span: start.to(self.scanner.last_span()),
+        })
+    }
+
+    // Generates AST for the block of a loop, in which break and continue may appear
+    fn generate_loop_block(&mut self) -> Result<Block, ASTError> {
+        self.loop_depth += 1;
+        let block = self.generate_block();
+        self.loop_depth -= 1;
+        block
+    }
+
+    // Skips ahead to the end of the statement an error was found in.
+    // stops after the next ; or block, or before a } closing the surrounding block.
+    fn sync_statement(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.scanner.peek_next() {
+                Token::Eof => return,
+                Token::Def if depth == 0 => return,
+                Token::RCurly if depth == 0 => return,
+                Token::SColon if depth == 0 => {
+                    self.skip_token();
+                    return;
+                }
+                Token::LCurly => depth += 1,
+                Token::RCurly => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.skip_token();
+                        return;
+                    }
+                }
+                _ => {}
             }
+            self.skip_token();
         }
-        Token::While => {
-            // consume cond
-            let cond = generate_exp(scanner)?;
-            // consume block
-            let block = generate_block(scanner)?;
-            NestKind::While { cond, block }
+    }
+
+    // Skips ahead to the next def or const, after an error outside any statement
+    fn sync_def(&mut self) {
+        while !self.check(TokenDiscriminants::Def)
+            && !self.check(TokenDiscriminants::Const)
+            && !self.check(TokenDiscriminants::Eof)
+        {
+            self.skip_token();
         }
-        _ => {
-            return Err(ASTError::UnexpectedToken(next));
+    }
+
+    // Consumes the next token while recovering from an error,
+    // further scanner errors are recorded so that they are still reported.
+    fn skip_token(&mut self) {
+        if let Err(err) = self.next_token() {
+            self.errors.push(err);
         }
-    };
+    }
 
-    Ok(Nest { nest })
-}
+    // Consumes the next token, whatever it is
+    fn next_token(&mut self) -> Result<Token, ASTError> {
+        let next = self.scanner.next_token()?;
+        self.expected.clear();
+        Ok(next)
+    }
 
-// Generates AST for Infix expression
-fn generate_infix(lhs: ExpKind, op: OpKind, rhs: Exp) -> Result<Exp, ASTError> {
-    let src_ln = rhs.src_ln;
-    Ok(Exp {
-        exp: Box::new(ExpKind::Infix(Exp { exp: Box::new(lhs), src_ln }, Op { op }, rhs)),
-        src_ln,
-    })
-}
+    // Returns true if the next token matches the given variant,
+    // otherwise remembers that variant was expected here
+    fn check(&mut self, variant: TokenDiscriminants) -> bool {
+        if variant_equal(&self.scanner.peek_next(), variant) {
+            return true;
+        }
+        self.expected.push(Expected::Token(variant));
+        false
+    }
+
+    // Consumes a token from the scanner specified by variant.
+    // Returns an ASTError if the next token was not the expected token, leaving it unconsumed.
+    fn consume_token(&mut self, variant: TokenDiscriminants) -> Result<Token, ASTError> {
+        if self.check(variant) {
+            return self.next_token();
+        }
+        Err(self.unexpected())
+    }
 
-// Consumes a token from the scanner specified by variant.
-// Returns an ASTError if the next token was not the expected token.
-fn consume_token(scanner: &mut Scanner, variant: TokenDiscriminants) -> Result<Token, ASTError> {
-    let next = scanner.next_token()?;
-    if variant_equal(&next, variant) {
-        return Ok(next);
-    }
-    // todo: remove
-    // let disc: TokenDiscriminants = next.clone().into();
-    // eprintln!("expected: {:?}, got: {:?}", variant, disc);
-    Err(ASTError::UnexpectedToken(next))
+    // Returns an error for the next token, which is none of the expected tokens
+    fn unexpected(&mut self) -> ASTError {
+        let mut expected = std::mem::take(&mut self.expected);
+        expected.sort();
+        expected.dedup();
+        ASTError::UnexpectedToken {
+            found: self.scanner.peek_next(),
+            expected,
+            span: self.scanner.peek_span(),
+        }
+    }
 }
 
 // Returns true if the given token matches the given variant
@@ -441,3 +814,21 @@ fn variant_equal(token: &Token, variant: TokenDiscriminants) -> bool {
     let disc: TokenDiscriminants = token.clone().into();
     disc == variant
 }
+
+// returns the name and index path of exp if it can be assigned to, e.i. it is a name indexed one or more times
+fn lvalue(exp: &Exp) -> Option<(String, Vec<Exp>)> {
+    let mut path = Vec::new();
+    let mut exp = exp;
+    while let ExpKind::ArrayAccess { array, index } = &*exp.exp {
+        path.push(index.clone());
+        exp = array;
+    }
+    match &*exp.exp {
+        ExpKind::Name(name) if !path.is_empty() => {
+            // the path was collected from the last index back
+            path.reverse();
+            Some((name.clone(), path))
+        }
+        _ => None,
+    }
+}
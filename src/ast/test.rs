@@ -4,7 +4,45 @@
 mod test {
 
     use super::super::*;
+    use crate::span::Span;
 
+    // parses `exp` as the return value of main and renders it fully parenthesized
+    fn parse_exp(exp: &str) -> String {
+        let mut scan = Scanner::new(format!("def main() {{ return {}; }}", exp)).unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        match &program.defs[0].block.statements[0].statement {
+            StatementKind::Return(exp) => render(exp),
+            other => panic!("expected return, got {:?}", other),
+        }
+    }
+
+    fn render(exp: &Exp) -> String {
+        match &*exp.exp {
+            ExpKind::Name(name) => name.clone(),
+            ExpKind::Num(value) => value.to_string(),
+            ExpKind::Infix(lhs, op, rhs) => {
+                let op = match &op.op {
+                    OpKind::Logical(Logical { logical: LogicalKind::Or }) => "||",
+                    OpKind::Logical(Logical { logical: LogicalKind::And }) => "&&",
+                    OpKind::Comparison(_) => "cmp",
+                    OpKind::Plus => "+",
+                    OpKind::Minus => "-",
+                    OpKind::Mul => "*",
+                    OpKind::Div => "/",
+                    OpKind::Mod => "%",
+                };
+                format!("({} {} {})", render(lhs), op, render(rhs))
+            }
+            ExpKind::Unary(Unop { unop: UnopKind::Neg }, exp) => format!("(-{})", render(exp)),
+            ExpKind::Unary(Unop { unop: UnopKind::Not }, exp) => format!("(!{})", render(exp)),
+            ExpKind::Array(exps) => {
+                let elements: Vec<String> = exps.exps.iter().map(render).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            ExpKind::ArrayAccess { array, index } => format!("{}[{}]", render(array), render(index)),
+            other => format!("{:?}", other),
+        }
+    }
 
     #[test]
     fn test_simple_ast() {
@@ -22,4 +60,265 @@ mod test {
         
         generate_ast(&mut scan).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(parse_exp("2 * 3 + 4"), "((2 * 3) + 4)");
+        assert_eq!(parse_exp("2 + 3 * 4"), "(2 + (3 * 4))");
+        assert_eq!(parse_exp("a || b && c == 1 + 2 % 3"), "(a || (b && (c cmp (1 + (2 % 3)))))");
+        assert_eq!(parse_exp("-a * b"), "((-a) * b)");
+        assert_eq!(parse_exp("!a && b"), "((!a) && b)");
+        assert_eq!(parse_exp("(2 + 3) * 4"), "((2 + 3) * 4)");
+    }
+
+    #[test]
+    fn test_left_associative() {
+        assert_eq!(parse_exp("10 - 2 - 3"), "((10 - 2) - 3)");
+        assert_eq!(parse_exp("8 / 4 / 2"), "((8 / 4) / 2)");
+        assert_eq!(parse_exp("a || b || c"), "((a || b) || c)");
+    }
+
+    #[test]
+    fn test_indexing() {
+        assert_eq!(parse_exp("-m[1][2] * 2"), "((-m[1][2]) * 2)");
+        assert_eq!(parse_exp("[1, a + 2][0]"), "[1, (a + 2)][0]");
+        assert_eq!(parse_exp("[[], [b]]"), "[[], [b]]");
+    }
+
+    #[test]
+    fn test_lvalue_path() {
+        let mut scan = Scanner::new(String::from("def main() { m[i][j + 1] := 3; m[0]; }")).unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        let statements = &program.defs[0].block.statements;
+        match &statements[0].statement {
+            StatementKind::ArrayAssign { name, path, .. } => {
+                assert_eq!(name, "m");
+                let path: Vec<String> = path.iter().map(render).collect();
+                assert_eq!(path, vec!["i", "(j + 1)"]);
+            }
+            other => panic!("expected array assign, got {:?}", other),
+        }
+        match &statements[1].statement {
+            StatementKind::Exp(exp) => assert_eq!(render(exp), "m[0]"),
+            other => panic!("expected exp, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let mut scan = Scanner::new(String::from(
+            "def main() { if (a) { 1; } else if (b) { 2; } else if (c) { 3; } else { 4; } if (d) { 5; } else { 6; } }",
+        ))
+        .unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        let statements = &program.defs[0].block.statements;
+        match &statements[0].statement {
+            StatementKind::Nest(Nest {
+                nest: NestKind::IfChain { branches, else_ },
+                ..
+            }) => {
+                let conds: Vec<String> = branches.iter().map(|(cond, _)| render(cond)).collect();
+                assert_eq!(conds, vec!["a", "b", "c"]);
+                assert_eq!(else_.as_ref().unwrap().statements.len(), 1);
+            }
+            other => panic!("expected if chain, got {:?}", other),
+        }
+        // without an else if, an if/else stays as it was
+        match &statements[1].statement {
+            StatementKind::Nest(Nest {
+                nest: NestKind::IfElse { .. },
+                ..
+            }) => {}
+            other => panic!("expected if/else, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut scan = Scanner::new(String::from(
+            "def main() {\n    a := 1 + foo(2);\n    if (a) {\n        return a;\n    }\n}",
+        ))
+        .unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        let def = &program.defs[0];
+        assert_eq!(def.span, Span::new(0, 72, 1, 1));
+        assert_eq!(def.block.span, Span::new(11, 72, 1, 12));
+
+        let assign = &def.block.statements[0];
+        assert_eq!(assign.span, Span::new(17, 33, 2, 5));
+        match &assign.statement {
+            StatementKind::Assign { exp, .. } => {
+                assert_eq!(exp.span, Span::new(22, 32, 2, 10));
+                match &*exp.exp {
+                    // foo(2)
+                    ExpKind::Infix(_, _, rhs) => assert_eq!(rhs.span, Span::new(26, 32, 2, 14)),
+                    other => panic!("expected infix, got {:?}", other),
+                }
+            }
+            other => panic!("expected assign, got {:?}", other),
+        }
+
+        let nest = &def.block.statements[1];
+        assert_eq!(nest.span, Span::new(38, 70, 3, 5));
+        match &nest.statement {
+            StatementKind::Nest(inner) => assert_eq!(inner.span, nest.span),
+            other => panic!("expected nest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_span() {
+        let mut scan = Scanner::new(String::from("def main() {\n    return 1\n}")).unwrap();
+        match generate_ast(&mut scan) {
+            Err(ASTError::UnexpectedToken { found, expected, span }) => {
+                assert_eq!(found, Token::RCurly);
+                assert_eq!(
+                    expected,
+                    vec![Expected::Token(TokenDiscriminants::SColon), Expected::Operator]
+                );
+                assert_eq!(span, Span::new(26, 27, 3, 1));
+            }
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recovery() {
+        let mut scan = Scanner::new(String::from(
+            r#"
+            def first() {
+                a := 1 +;
+                b := 2;
+                if (b > ) {
+                    c := 3;
+                }
+                return b
+            }
+
+            def (broken) {
+                return 1;
+            }
+
+            def main() {
+                d := $ 4;
+                return first();
+            }
+            "#,
+        ))
+        .unwrap();
+        let (program, errors) = generate_ast_recovering(&mut scan);
+
+        // every error is reported, in source order
+        let lines: Vec<u32> = errors.iter().map(|err| err.span().line).collect();
+        assert_eq!(lines, vec![3, 5, 9, 11, 16]);
+        match &errors[0] {
+            ASTError::UnexpectedToken { found, expected, .. } => {
+                assert_eq!(*found, Token::SColon);
+                assert_eq!(*expected, vec![Expected::Expression]);
+            }
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+        match &errors[3] {
+            ASTError::UnexpectedToken { found, expected, .. } => {
+                assert_eq!(*found, Token::LParen);
+                assert_eq!(*expected, vec![Expected::Token(TokenDiscriminants::Name)]);
+            }
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+        assert!(matches!(errors[4], ASTError::ScannerError(_)));
+
+        // the statements and defs around each error are still parsed
+        let names: Vec<&str> = program.defs.iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "main"]);
+        assert_eq!(program.defs[0].block.statements.len(), 1);
+        assert_eq!(program.defs[1].block.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_recovery_missing_curly() {
+        let mut scan = Scanner::new(String::from(
+            r#"
+            def first() {
+                return 1;
+
+            def main() {
+                return 2;
+            }
+            "#,
+        ))
+        .unwrap();
+        let (program, errors) = generate_ast_recovering(&mut scan);
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            ASTError::UnexpectedToken { found, .. } => assert_eq!(*found, Token::Def),
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+        assert_eq!(program.defs.len(), 1);
+        assert_eq!(program.defs[0].name, "main");
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let mut scan = Scanner::new(String::from(
+            r#"
+            def main() {
+                while (1) {
+                    if (1) { break; }
+                }
+                continue;
+                for x in [1, 2] { if (x) { continue; } }
+                return 0;
+            }
+            "#,
+        ))
+        .unwrap();
+        let (program, errors) = generate_ast_recovering(&mut scan);
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            ASTError::OutsideLoop { found, span } => {
+                assert_eq!(*found, Token::Continue);
+                assert_eq!((span.line, span.col), (6, 17));
+            }
+            other => panic!("expected OutsideLoop, got {:?}", other),
+        }
+        // the rest of the def is still parsed
+        assert_eq!(program.defs[0].block.statements.len(), 3);
+    }
+
+    #[test]
+    fn test_array_size_literal() {
+        // [n] used to create an array of n zeros, it is an error rather than an array holding n
+        let mut scan = Scanner::new(String::from("def main() {\n  a := [5];\n  return a;\n}")).unwrap();
+        let (program, errors) = generate_ast_recovering(&mut scan);
+        match &errors[..] {
+            [ASTError::ArraySizeLiteral { size, span }] => {
+                assert_eq!(*size, 5f64);
+                assert_eq!((span.line, span.col, span.end - span.start), (2, 8, 3));
+            }
+            other => panic!("expected ArraySizeLiteral, got {:?}", other),
+        }
+        assert_eq!(program.defs[0].block.statements.len(), 1);
+
+        // any other single element is still an array holding it
+        for source in ["[(5)]", "[x]", "[5 + 1]", "[-5]", "[\"5\"]", "[5, 6]"] {
+            let mut scan = Scanner::new(format!("def main() {{ return {}; }}", source)).unwrap();
+            assert!(generate_ast(&mut scan).is_ok(), "{} should parse", source);
+        }
+    }
+
+    #[test]
+    fn test_consts() {
+        let mut scan = Scanner::new(String::from(
+            "const SIZE := 2 * 3;\ndef main() { return SIZE; }\nconst NAME := \"smp\";",
+        ))
+        .unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        assert_eq!(program.defs.len(), 1);
+        // consts keep the order they are written in, wherever they are among the defs
+        let names: Vec<&str> = program.consts.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["SIZE", "NAME"]);
+        assert_eq!(render(&program.consts[0].exp), "(2 * 3)");
+        let span = program.consts[1].span;
+        assert_eq!((span.line, span.col, span.end - span.start), (3, 1, 20));
+    }
+}
@@ -0,0 +1,393 @@
+#[cfg(test)]
+mod test;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::interpreter::helpers;
+use crate::interpreter::ops::BuiltinFn;
+use crate::interpreter::symbols::*;
+use crate::interpreter::MAIN;
+use crate::span::Span;
+
+// Problems found in a program before it runs, which would otherwise only be raised if execution reached them
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckError {
+    // a call to a name that no def or host function has
+    UnboundFunc(String, Span),
+    ArgMismatch {
+        name: String,
+        got: usize,
+        expected: usize,
+        span: Span,
+    },
+    // a variable read where, whichever path led there, it isn't in scope
+    UnboundVar(String, Span),
+    // a def with the same name as an earlier def, which replaces it
+    DuplicateDef {
+        name: String,
+        span: Span,
+        previous: Span,
+    },
+    // a def taking two params with the same name, only the last is bound. span is the repeated one
+    DuplicateParam {
+        name: String,
+        def: String,
+        span: Span,
+    },
+    // a def other than main that can end without returning a value, span is where it falls through
+    MissingReturn {
+        def: String,
+        span: Span,
+    },
+    MissingMain,
+    // main takes params, so it can only be run with as many arguments
+    MainParams(usize, Span),
+}
+
+impl CheckError {
+    // the source location of the problem, a placeholder if it isn't anywhere in particular
+    pub fn span(&self) -> Span {
+        match self {
+            CheckError::MissingMain => Span::default(),
+            CheckError::UnboundFunc(_, span)
+            | CheckError::ArgMismatch { span, .. }
+            | CheckError::UnboundVar(_, span)
+            | CheckError::DuplicateDef { span, .. }
+            | CheckError::DuplicateParam { span, .. }
+            | CheckError::MissingReturn { span, .. }
+            | CheckError::MainParams(_, span) => *span,
+        }
+    }
+
+    // whether the program can still run as intended despite the problem
+    pub fn is_warning(&self) -> bool {
+        matches!(self, CheckError::MainParams(..))
+    }
+}
+
+// Checks program without running it, returning its problems in source order
+pub fn check(program: &Program) -> Vec<CheckError> {
+    Checker::new(program).check()
+}
+
+// Checker walks a program, tracking which variables are in scope at each point, like the Interpreter's Environment
+pub struct Checker<'a> {
+    program: &'a Program,
+    // the arity of each function, by name. a later def replaces an earlier one
+    funcs: HashMap<&'a str, usize>,
+    // the names of consts that have been evaluated at the current point
+    consts: HashSet<&'a str>,
+    scopes: Vec<HashSet<&'a str>>,
+    errors: Vec<CheckError>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(program: &'a Program) -> Checker<'a> {
+        Checker {
+            program,
+            funcs: program
+                .defs
+                .iter()
+                .map(|def| (def.name.as_str(), def.args.names.len()))
+                .collect(),
+            consts: HashSet::new(),
+            scopes: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    // Lets calls to the host function name, taking arity args, through, see Interpreter::register_fn.
+    // defs take precedence over it
+    pub fn with_host_fn(mut self, name: &'a str, arity: usize) -> Checker<'a> {
+        self.funcs.entry(name).or_insert(arity);
+        self
+    }
+
+    pub fn check(mut self) -> Vec<CheckError> {
+        // consts are evaluated in order before main, each can only use those before it
+        for c in &self.program.consts {
+            self.check_exp(&c.exp);
+            self.consts.insert(&c.name);
+        }
+
+        let mut defs: HashMap<&str, &Def> = HashMap::new();
+        for def in &self.program.defs {
+            if let Some(previous) = defs.insert(&def.name, def) {
+                self.errors.push(CheckError::DuplicateDef {
+                    name: def.name.clone(),
+                    span: def.span,
+                    previous: previous.span,
+                });
+            }
+            self.check_def(def);
+        }
+
+        // the def called is the last one with the name
+        match defs.get(MAIN) {
+            None => self.errors.push(CheckError::MissingMain),
+            Some(main) if !main.args.names.is_empty() => {
+                self.errors.push(CheckError::MainParams(main.args.names.len(), main.span))
+            }
+            Some(_) => {}
+        }
+
+        // problems without a location come last
+        self.errors.sort_by_key(|err| {
+            let span = err.span();
+            (span.is_dummy(), span.start)
+        });
+        self.errors
+    }
+
+    fn check_def(&mut self, def: &'a Def) {
+        let mut params = HashSet::new();
+        for (name, span) in def.args.names.iter().zip(&def.args.spans) {
+            if !params.insert(name.as_str()) {
+                self.errors.push(CheckError::DuplicateParam {
+                    name: name.clone(),
+                    def: def.name.clone(),
+                    span: *span,
+                });
+            }
+        }
+
+        self.scopes = vec![params];
+        self.check_block(&def.block);
+        self.scopes.clear();
+
+        // main may end without a value, every other def is called for one
+        if def.name != MAIN {
+            if let Some(span) = fall_through(&def.block) {
+                self.errors.push(CheckError::MissingReturn {
+                    def: def.name.clone(),
+                    span,
+                });
+            }
+        }
+    }
+
+    // checks a block in a scope of its own
+    fn check_block(&mut self, block: &'a Block) {
+        self.scoped(|checker| {
+            for statement in &block.statements {
+                checker.check_statement(statement);
+            }
+        });
+    }
+
+    // checks with a new innermost scope, which is dropped afterwards
+    fn scoped(&mut self, check: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashSet::new());
+        check(self);
+        self.scopes.pop();
+    }
+
+    fn check_statement(&mut self, statement: &'a Statement) {
+        match &statement.statement {
+            StatementKind::Return(exp) | StatementKind::Exp(exp) => self.check_exp(exp),
+            StatementKind::Let { name, exp } => {
+                self.check_exp(exp);
+                self.declare(name);
+            }
+            StatementKind::Assign { name, exp } => {
+                self.check_exp(exp);
+                // assigning a variable not in scope declares it, unless it is a const
+                if !self.in_scope(name) && !self.consts.contains(name.as_str()) {
+                    self.declare(name);
+                }
+            }
+            StatementKind::ArrayAssign { name, path, value } => {
+                self.check_var(name, statement.span);
+                for index in path {
+                    self.check_exp(index);
+                }
+                self.check_exp(value);
+            }
+            StatementKind::Nest(nest) => self.check_nest(nest),
+            StatementKind::Break | StatementKind::Continue => {}
+        }
+    }
+
+    fn check_nest(&mut self, nest: &'a Nest) {
+        match &nest.nest {
+            NestKind::If { cond, then } => {
+                self.check_exp(cond);
+                self.check_block(then);
+            }
+            NestKind::IfElse { cond, then, else_ } => {
+                self.check_exp(cond);
+                self.check_block(then);
+                self.check_block(else_);
+            }
+            NestKind::IfChain { branches, else_ } => {
+                for (cond, block) in branches {
+                    self.check_exp(cond);
+                    self.check_block(block);
+                }
+                if let Some(else_) = else_ {
+                    self.check_block(else_);
+                }
+            }
+            NestKind::While { cond, block } => {
+                self.check_exp(cond);
+                self.check_block(block);
+            }
+            NestKind::For {
+                init,
+                cond,
+                step,
+                block,
+            } => self.scoped(|checker| {
+                checker.check_statement(init);
+                checker.check_exp(cond);
+                checker.check_block(block);
+                checker.check_statement(step);
+            }),
+            NestKind::ForIn {
+                name,
+                iterable,
+                block,
+            } => {
+                self.check_exp(iterable);
+                self.scoped(|checker| {
+                    checker.declare(name);
+                    checker.check_block(block);
+                });
+            }
+        }
+    }
+
+    fn check_exp(&mut self, exp: &'a Exp) {
+        match &*exp.exp {
+            ExpKind::Name(name) => self.check_var(name, exp.span),
+            ExpKind::Num(_) | ExpKind::Str(_) | ExpKind::Bool(_) => {}
+            ExpKind::Array(exps) => self.check_exps(exps),
+            ExpKind::Infix(lhs, _, rhs) => {
+                self.check_exp(lhs);
+                self.check_exp(rhs);
+            }
+            ExpKind::ArrayAccess { array, index } => {
+                self.check_exp(array);
+                self.check_exp(index);
+            }
+            ExpKind::Call(name, exps) => {
+                self.check_exps(exps);
+                match self.funcs.get(name.as_str()) {
+                    None => self.errors.push(CheckError::UnboundFunc(name.clone(), exp.span)),
+                    Some(&expected) if expected != exps.exps.len() => self.errors.push(CheckError::ArgMismatch {
+                        name: name.clone(),
+                        got: exps.exps.len(),
+                        expected,
+                        span: exp.span,
+                    }),
+                    Some(_) => {}
+                }
+            }
+            ExpKind::BuiltIn(builtin) => {
+                let (func, exps) = BuiltinFn::of(&builtin.builtin);
+                if func.arity() != exps.exps.len() {
+                    self.errors.push(CheckError::ArgMismatch {
+                        name: func.name().to_string(),
+                        got: exps.exps.len(),
+                        expected: func.arity(),
+                        span: exp.span,
+                    });
+                }
+                self.check_exps(exps);
+            }
+            ExpKind::Paren(exp) | ExpKind::Unary(_, exp) => self.check_exp(exp),
+        }
+    }
+
+    fn check_exps(&mut self, exps: &'a Exps) {
+        for exp in &exps.exps {
+            self.check_exp(exp);
+        }
+    }
+
+    // checks that the variable name can be read at span
+    fn check_var(&mut self, name: &str, span: Span) {
+        if !self.in_scope(name) && !self.consts.contains(name) {
+            self.errors.push(CheckError::UnboundVar(name.to_string(), span));
+        }
+    }
+
+    fn in_scope(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    // declares name in the innermost scope
+    fn declare(&mut self, name: &'a str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name);
+        }
+    }
+}
+
+// Finds where control can reach the end of block without returning,
+// None if every path through it returns, or never ends
+fn fall_through(block: &Block) -> Option<Span> {
+    let mut end = block.span;
+    for statement in &block.statements {
+        match statement_fall_through(statement) {
+            // anything after it can't run
+            None => return None,
+            Some(span) => end = span,
+        }
+    }
+    Some(end)
+}
+
+fn statement_fall_through(statement: &Statement) -> Option<Span> {
+    match &statement.statement {
+        StatementKind::Return(_) => None,
+        StatementKind::Nest(nest) => match &nest.nest {
+            // the cond may not hold
+            NestKind::If { .. } => Some(statement.span),
+            NestKind::IfElse { then, else_, .. } => fall_through(then).or_else(|| fall_through(else_)),
+            NestKind::IfChain { branches, else_ } => branches
+                .iter()
+                .find_map(|(_, block)| fall_through(block))
+                .or_else(|| match else_ {
+                    Some(else_) => fall_through(else_),
+                    None => Some(statement.span),
+                }),
+            // a loop that always goes around can only be left by a return or a break
+            NestKind::While { cond, block } | NestKind::For { cond, block, .. }
+                if always_true(cond) && !breaks(block) =>
+            {
+                None
+            }
+            // otherwise it may run to the end, or not run at all
+            NestKind::While { .. } | NestKind::For { .. } | NestKind::ForIn { .. } => Some(statement.span),
+        },
+        _ => Some(statement.span),
+    }
+}
+
+// whether cond holds whatever the state, when strict conditions are off.
+// a number when they are on raises an error, so it still doesn't end the loop
+fn always_true(cond: &Exp) -> bool {
+    match &*cond.exp {
+        ExpKind::Bool(value) => *value,
+        ExpKind::Num(value) => helpers::truthy(*value),
+        ExpKind::Paren(exp) => always_true(exp),
+        _ => false,
+    }
+}
+
+// whether block, the body of a loop, has a break leaving that loop rather than one nested in it
+fn breaks(block: &Block) -> bool {
+    block.statements.iter().any(|statement| match &statement.statement {
+        StatementKind::Break => true,
+        StatementKind::Nest(nest) => match &nest.nest {
+            NestKind::If { then, .. } => breaks(then),
+            NestKind::IfElse { then, else_, .. } => breaks(then) || breaks(else_),
+            NestKind::IfChain { branches, else_ } => {
+                branches.iter().any(|(_, block)| breaks(block)) || else_.as_ref().is_some_and(breaks)
+            }
+            NestKind::While { .. } | NestKind::For { .. } | NestKind::ForIn { .. } => false,
+        },
+        _ => false,
+    })
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::*;
+    use crate::ast::generate_ast;
+    use crate::scanner::Scanner;
+
+    fn check_source(source: &str) -> Vec<CheckError> {
+        let mut scan = Scanner::new(source.to_string()).unwrap();
+        check(&generate_ast(&mut scan).unwrap())
+    }
+
+    #[test]
+    fn test_valid_program() {
+        let errors = check_source(
+            "const N := 3;
+             def sum(arr) { total := 0; for x in arr { total := total + x; } return total; }
+             def main() {
+                 let a := array(N);
+                 for (i := 0; i < N; i := i + 1) { a[i] := i; }
+                 if (sum(a) > 1) { b := 1; b; } else { b := 2; b; }
+                 return sum(a);
+             }",
+        );
+        assert_eq!(errors, Vec::new());
+    }
+
+    #[test]
+    fn test_unbound_vars() {
+        let errors = check_source(
+            "const A := B;
+             const B := 1;
+             def main() {
+                 if (B) { x := 1; }
+                 for y in [1, 2] { }
+                 return x + y + B;
+             }",
+        );
+        let names: Vec<String> = errors
+            .into_iter()
+            .map(|err| match err {
+                CheckError::UnboundVar(name, _) => name,
+                other => panic!("expected UnboundVar, got {:?}", other),
+            })
+            .collect();
+        // consts can only use earlier consts, variables go away with the block they were declared in
+        assert_eq!(names, ["B", "x", "y"]);
+    }
+
+    #[test]
+    fn test_calls() {
+        let errors = check_source("def f(a) { return a; } def main() { return f() + g(1) + len(1, 2); }");
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(&errors[0], CheckError::ArgMismatch { name, got: 0, expected: 1, .. } if name == "f"));
+        assert!(matches!(&errors[1], CheckError::UnboundFunc(name, _) if name == "g"));
+        assert!(matches!(&errors[2], CheckError::ArgMismatch { name, got: 2, expected: 1, .. } if name == "len"));
+
+        // host functions can be let through
+        let mut scan = Scanner::new(String::from("def main() { return g(1); }")).unwrap();
+        let program = generate_ast(&mut scan).unwrap();
+        assert_eq!(Checker::new(&program).with_host_fn("g", 1).check(), Vec::new());
+    }
+
+    #[test]
+    fn test_defs() {
+        let errors = check_source("def f(a, a) { return a; }\ndef f(a) { return a; }");
+        match &errors[..] {
+            [CheckError::DuplicateParam { name, def, span: param }, CheckError::DuplicateDef { span, previous, .. }, CheckError::MissingMain] => {
+                assert_eq!((name.as_str(), def.as_str()), ("a", "f"));
+                // the second a
+                assert_eq!((param.line, param.col, param.end - param.start), (1, 10, 1));
+                assert_eq!((span.line, previous.line), (2, 1));
+            }
+            other => panic!("unexpected errors {:?}", other),
+        }
+
+        let errors = check_source("def main(args) { return len(args); }");
+        assert!(matches!(&errors[..], [CheckError::MainParams(1, _)]));
+        assert!(errors[0].is_warning());
+    }
+
+    #[test]
+    fn test_missing_returns() {
+        let errors = check_source(
+            "def a(x) { if (x) { return 1; } }
+             def b(x) { if (x) { return 1; } else { return 2; } }
+             def c(x) { if (x) { return 1; } else if (x > 1) { x := 2; } else { return 3; } }
+             def d() { while (true) { if (false) { return 1; } } }
+             def e() { while (true) { break; } }
+             def f(x) { for y in x { return y; } }
+             def g() { }
+             def h() { return 1; 2; }
+             def main() { }",
+        );
+        let spans: Vec<(&str, u32)> = errors
+            .iter()
+            .map(|err| match err {
+                CheckError::MissingReturn { def, span } => (def.as_str(), span.line),
+                other => panic!("expected MissingReturn, got {:?}", other),
+            })
+            .collect();
+        // a loop that always goes around without a break only ends by returning, as does anything after a return
+        assert_eq!(spans, [("a", 1), ("c", 3), ("e", 5), ("f", 6), ("g", 7)]);
+        assert_eq!(errors[1].span().col, 64);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::interpreter::errors::InterpreterError;
+use crate::interpreter::ops::{BinaryOp, BuiltinFn};
+use crate::interpreter::symbols::{Exp, UnopKind};
+use crate::interpreter::Value;
+use crate::span::Span;
+
+// Instr is a single vm instruction.
+// operands are popped off the value stack, results are pushed back onto it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    // push a constant from the Module's constant pool
+    Const(usize),
+    // push the value of a local slot, fails if it was never assigned
+    Load(usize),
+    // pop a value into a local slot
+    Store(usize),
+    // push the value of a global const, fails if it wasn't evaluated yet
+    LoadGlobal(usize),
+    // pop a value into a global const's slot
+    StoreGlobal(usize),
+    // pop a value into the slot of a variable assigned without being declared,
+    // fails under strict declarations
+    StoreUndeclared(usize),
+    // pop rhs then lhs, push lhs op rhs
+    Binary(BinaryOp),
+    Unary(UnopKind),
+    // jump to an instruction in the current function
+    Jump(usize),
+    // pop a condition, jump if it is truthy / not truthy
+    JumpIfTrue(usize),
+    JumpIfFalse(usize),
+    // pop a position then an array or string, push the element at that position,
+    // or jump if the position is past the end
+    Iterate(usize),
+    // pop that many elements, push an array of them in the order they were pushed
+    Array(usize),
+    // pop an index then an array, push the element
+    Index,
+    // pop a value, an index then an array, and update the array in place
+    SetIndex,
+    // call a function, its args are on top of the stack.
+    // site is the call expression, for reporting valueless calls
+    Call { func: usize, site: usize },
+    // call the host function named host_names[name] with that many args on top of the stack,
+    // fails if the vm has none registered with the name
+    CallHost { name: usize, args: usize },
+    // call a built-in function, its args are on top of the stack
+    BuiltIn(BuiltinFn),
+    // pop a value and print it
+    Print,
+    // pop a value and return it to the caller
+    Return,
+    // return to the caller without a value
+    ReturnNone,
+    // raise an error found while compiling, once it is reached
+    Fail(usize),
+}
+
+// An error the compiler found, raised only if the program reaches it (like the Interpreter would)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    UnboundVar(String),
+    ConstAssign(String),
+    ArgMismatch { got: usize, expected: usize },
+}
+
+impl Failure {
+    pub fn to_error(&self) -> InterpreterError {
+        match self {
+            Failure::UnboundVar(name) => InterpreterError::UnboundVar(name.clone(), Span::default()),
+            Failure::ConstAssign(name) => InterpreterError::ConstAssign(name.clone(), Span::default()),
+            Failure::ArgMismatch { got, expected } => InterpreterError::ArgMismatch {
+                got: *got,
+                expected: *expected,
+                span: Span::default(),
+            },
+        }
+    }
+}
+
+// Function is a compiled def
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    // the name of each local slot, the args come first
+    pub locals: Vec<String>,
+    pub code: Vec<Instr>,
+    // the source span of each instruction in code, errors raised by it are reported there
+    pub spans: Vec<Span>,
+}
+
+// Module is a compiled program
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+    pub constants: Vec<Value>,
+    pub call_sites: Vec<Exp>,
+    pub failures: Vec<Failure>,
+    // the index of main in functions, if the program has one
+    pub main: Option<usize>,
+    // the names of functions called without a def, looked up among the host functions when called
+    pub host_names: Vec<String>,
+    // the name of each global const, by slot
+    pub globals: Vec<String>,
+    // the index in functions of the function evaluating the consts, if the program has any
+    pub init: Option<usize>,
+}
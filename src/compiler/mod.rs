@@ -0,0 +1,550 @@
+pub mod bytecode;
+
+#[cfg(test)]
+mod test;
+
+use std::collections::{HashMap, HashSet};
+
+use bytecode::*;
+use crate::interpreter::ops::{BinaryOp, BuiltinFn};
+use crate::interpreter::symbols::*;
+use crate::interpreter::{Value, MAIN};
+use crate::span::Span;
+
+// name of the function that evaluates a program's consts, which no def can have
+const CONSTS: &str = "<consts>";
+
+// Compiles a program into a Module the vm can run.
+// compiling never fails, errors the Interpreter would raise are compiled into Fail instructions,
+// so that they are only raised if the program reaches them.
+pub fn compile(program: &Program) -> Module {
+    // like the Interpreter, a later def replaces an earlier def with the same name
+    let mut defs: Vec<&Def> = Vec::new();
+    let mut funcs: HashMap<&str, usize> = HashMap::new();
+    for def in &program.defs {
+        match funcs.get(def.name.as_str()) {
+            Some(&func) => defs[func] = def,
+            None => {
+                funcs.insert(&def.name, defs.len());
+                defs.push(def);
+            }
+        }
+    }
+
+    // each const name gets a global slot, in the order they are first declared
+    let mut globals: HashMap<&str, usize> = HashMap::new();
+    for c in &program.consts {
+        let slot = globals.len();
+        globals.entry(&c.name).or_insert(slot);
+    }
+
+    let mut compiler = Compiler {
+        funcs: defs
+            .iter()
+            .map(|def| (def.name.as_str(), def.args.names.len()))
+            .enumerate()
+            .map(|(func, (name, arity))| (name, (func, arity)))
+            .collect(),
+        globals,
+        module: Module::default(),
+        constants: HashMap::new(),
+    };
+
+    for def in defs {
+        let function = compiler.compile_def(def);
+        compiler.module.functions.push(function);
+    }
+    compiler.module.main = compiler.funcs.get(MAIN).map(|&(func, _)| func);
+
+    let mut globals = vec![String::new(); compiler.globals.len()];
+    for (name, &slot) in &compiler.globals {
+        globals[slot] = name.to_string();
+    }
+    compiler.module.globals = globals;
+    if !program.consts.is_empty() {
+        let function = compiler.compile_consts(&program.consts);
+        compiler.module.functions.push(function);
+        compiler.module.init = Some(compiler.module.functions.len() - 1);
+    }
+    compiler.module
+}
+
+// Compiler holds what is shared between the functions of a Module while it is being compiled
+struct Compiler<'a> {
+    // function index and arity of each def, by name
+    funcs: HashMap<&'a str, (usize, usize)>,
+    // global slot of each const, by name
+    globals: HashMap<&'a str, usize>,
+    module: Module,
+    // index of each constant in the pool
+    constants: HashMap<Constant, usize>,
+}
+
+// Constant identifies a value in the constant pool
+#[derive(PartialEq, Eq, Hash)]
+enum Constant {
+    // by its bits, as f64 isn't Eq
+    Num(u64),
+    Str(String),
+    Bool(bool),
+}
+
+// FunctionBuilder is a function being compiled
+struct FunctionBuilder {
+    locals: Vec<String>,
+    // the slot of each variable in scope, by name, for each block being compiled, innermost last
+    scopes: Vec<HashMap<String, usize>>,
+    code: Vec<Instr>,
+    spans: Vec<Span>,
+    // the loops being compiled, innermost last
+    loops: Vec<Loop>,
+}
+
+// Loop holds the jumps of the break and continue statements in a loop, to patch once its ends are known
+#[derive(Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+impl<'a> Compiler<'a> {
+    fn compile_def(&mut self, def: &Def) -> Function {
+        let mut builder = FunctionBuilder::new(&def.args.names);
+
+        self.compile_block(&def.block, &mut builder);
+        // falling off the end returns nothing
+        builder.emit(Instr::ReturnNone, def.block.span);
+
+        Function {
+            name: def.name.clone(),
+            arity: def.args.names.len(),
+            locals: builder.locals,
+            code: builder.code,
+            spans: builder.spans,
+        }
+    }
+
+    // compiles the consts into the function that runs before main, storing each in its global slot
+    fn compile_consts(&mut self, consts: &[Const]) -> Function {
+        let mut builder = FunctionBuilder::new(&[]);
+        let mut bound = HashSet::new();
+        for c in consts {
+            self.compile_exp(&c.exp, &mut builder);
+            // like the Interpreter, a second const with the same name fails once its value is known
+            let instr = if bound.insert(c.name.as_str()) {
+                Instr::StoreGlobal(self.globals[c.name.as_str()])
+            } else {
+                self.fail(Failure::ConstAssign(c.name.clone()))
+            };
+            builder.emit(instr, c.span);
+        }
+        builder.emit(Instr::ReturnNone, Span::default());
+
+        Function {
+            name: String::from(CONSTS),
+            arity: 0,
+            locals: builder.locals,
+            code: builder.code,
+            spans: builder.spans,
+        }
+    }
+
+    fn compile_block(&mut self, block: &Block, builder: &mut FunctionBuilder) {
+        builder.scopes.push(HashMap::new());
+        for statement in &block.statements {
+            self.compile_statement(statement, builder);
+        }
+        builder.scopes.pop();
+    }
+
+    fn compile_statement(&mut self, statement: &Statement, builder: &mut FunctionBuilder) {
+        let span = statement.span;
+        match &statement.statement {
+            StatementKind::Return(exp) => {
+                self.compile_exp(exp, builder);
+                builder.emit(Instr::Return, span);
+            }
+            StatementKind::Let { name, exp } => {
+                self.compile_exp(exp, builder);
+                let slot = builder.declare(name);
+                builder.emit(Instr::Store(slot), span);
+            }
+            StatementKind::Assign { name, exp } => {
+                self.compile_exp(exp, builder);
+                // an undeclared variable is declared by assigning it, unless it is a const or declarations are strict
+                let instr = match builder.resolve(name) {
+                    Some(slot) => Instr::Store(slot),
+                    None if self.globals.contains_key(name.as_str()) => {
+                        self.fail(Failure::ConstAssign(name.clone()))
+                    }
+                    None => Instr::StoreUndeclared(builder.declare(name)),
+                };
+                builder.emit(instr, span);
+            }
+            StatementKind::ArrayAssign { name, path, value } => {
+                let (last, inner) = path.split_last().expect("an lvalue has at least one index");
+                // nor can the elements of a const's array be assigned through the const
+                let instr = match builder.resolve(name) {
+                    None if self.globals.contains_key(name.as_str()) => {
+                        self.fail(Failure::ConstAssign(name.clone()))
+                    }
+                    _ => self.load(name, builder),
+                };
+                builder.emit(instr, span);
+                for index in inner {
+                    self.compile_exp(index, builder);
+                    builder.emit(Instr::Index, span);
+                }
+                self.compile_exp(last, builder);
+                self.compile_exp(value, builder);
+                builder.emit(Instr::SetIndex, span);
+            }
+            StatementKind::Exp(exp) => {
+                self.compile_exp(exp, builder);
+                builder.emit(Instr::Print, span);
+            }
+            StatementKind::Nest(nest) => self.compile_nest(nest, span, builder),
+            StatementKind::Break => {
+                let jump = builder.emit(Instr::Jump(0), span);
+                builder.innermost_loop().breaks.push(jump);
+            }
+            StatementKind::Continue => {
+                let jump = builder.emit(Instr::Jump(0), span);
+                builder.innermost_loop().continues.push(jump);
+            }
+        }
+    }
+
+    // conditions are reported at span, the span of the statement holding the nest
+    fn compile_nest(&mut self, nest: &Nest, span: Span, builder: &mut FunctionBuilder) {
+        match &nest.nest {
+            NestKind::If { cond, then } => {
+                self.compile_exp(cond, builder);
+                let skip = builder.emit(Instr::JumpIfFalse(0), span);
+                self.compile_block(then, builder);
+                builder.patch(skip);
+            }
+            NestKind::IfElse { cond, then, else_ } => {
+                self.compile_exp(cond, builder);
+                let to_else = builder.emit(Instr::JumpIfFalse(0), span);
+                self.compile_block(then, builder);
+                let to_end = builder.emit(Instr::Jump(0), span);
+                builder.patch(to_else);
+                self.compile_block(else_, builder);
+                builder.patch(to_end);
+            }
+            NestKind::IfChain { branches, else_ } => {
+                // each branch that doesn't run falls through to the next one's condition
+                let mut to_end = Vec::new();
+                for (cond, block) in branches {
+                    self.compile_exp(cond, builder);
+                    let to_next = builder.emit(Instr::JumpIfFalse(0), span);
+                    self.compile_block(block, builder);
+                    to_end.push(builder.emit(Instr::Jump(0), span));
+                    builder.patch(to_next);
+                }
+                if let Some(else_) = else_ {
+                    self.compile_block(else_, builder);
+                }
+                builder.patch_all(&to_end, builder.code.len());
+            }
+            NestKind::While { cond, block } => {
+                let start = builder.code.len();
+                self.compile_exp(cond, builder);
+                let exit = builder.emit(Instr::JumpIfFalse(0), span);
+                let jumps = self.compile_loop_block(block, builder);
+                builder.patch_all(&jumps.continues, start);
+                builder.emit(Instr::Jump(start), span);
+                builder.patch(exit);
+                builder.patch_all(&jumps.breaks, builder.code.len());
+            }
+            NestKind::For {
+                init,
+                cond,
+                step,
+                block,
+            } => {
+                // variables declared in the header belong to the loop
+                builder.scopes.push(HashMap::new());
+                self.compile_statement(init, builder);
+                let start = builder.code.len();
+                self.compile_exp(cond, builder);
+                let exit = builder.emit(Instr::JumpIfFalse(0), span);
+                let jumps = self.compile_loop_block(block, builder);
+                // continue still runs the step
+                builder.patch_all(&jumps.continues, builder.code.len());
+                self.compile_statement(step, builder);
+                builder.emit(Instr::Jump(start), span);
+                builder.patch(exit);
+                builder.patch_all(&jumps.breaks, builder.code.len());
+                builder.scopes.pop();
+            }
+            NestKind::ForIn {
+                name,
+                iterable,
+                block,
+            } => {
+                // the iterable and the position reached in it are kept in locals no variable can name
+                let iterable_slot = builder.hidden();
+                let position_slot = builder.hidden();
+                self.compile_exp(iterable, builder);
+                builder.emit(Instr::Store(iterable_slot), span);
+                let zero = self.constant(0.0);
+                builder.emit(Instr::Const(zero), span);
+                builder.emit(Instr::Store(position_slot), span);
+
+                // each iteration assigns the next element, then moves the position past it
+                let start = builder.code.len();
+                builder.emit(Instr::Load(iterable_slot), span);
+                builder.emit(Instr::Load(position_slot), span);
+                let exit = builder.emit(Instr::Iterate(0), span);
+                // the loop variable belongs to the loop, shadowing any outer variable with the same name
+                builder.scopes.push(HashMap::new());
+                let slot = builder.declare(name);
+                builder.emit(Instr::Store(slot), span);
+                let one = self.constant(1.0);
+                builder.emit(Instr::Load(position_slot), span);
+                builder.emit(Instr::Const(one), span);
+                builder.emit(Instr::Binary(BinaryOp::Add), span);
+                builder.emit(Instr::Store(position_slot), span);
+
+                let jumps = self.compile_loop_block(block, builder);
+                builder.scopes.pop();
+                builder.patch_all(&jumps.continues, start);
+                builder.emit(Instr::Jump(start), span);
+                builder.patch(exit);
+                builder.patch_all(&jumps.breaks, builder.code.len());
+            }
+        }
+    }
+
+    // compiles the block of a loop, returning the jumps of the break and continue statements in it
+    fn compile_loop_block(&mut self, block: &Block, builder: &mut FunctionBuilder) -> Loop {
+        builder.loops.push(Loop::default());
+        self.compile_block(block, builder);
+        builder.loops.pop().expect("the loop was pushed")
+    }
+
+    fn compile_exp(&mut self, exp: &Exp, builder: &mut FunctionBuilder) {
+        let span = exp.span;
+        match &*exp.exp {
+            ExpKind::Name(name) => {
+                let instr = self.load(name, builder);
+                builder.emit(instr, span);
+            }
+            ExpKind::Num(value) => {
+                let constant = self.constant(*value);
+                builder.emit(Instr::Const(constant), span);
+            }
+            ExpKind::Bool(value) => {
+                let constant = self.boolean(*value);
+                builder.emit(Instr::Const(constant), span);
+            }
+            ExpKind::Str(value) => {
+                let constant = self.intern(Constant::Str(value.clone()), || Value::from(value.as_str()));
+                builder.emit(Instr::Const(constant), span);
+            }
+            ExpKind::Infix(lhs, op, rhs) => match &op.op {
+                OpKind::Logical(logical) => self.compile_logical(lhs, logical, rhs, span, builder),
+                op => {
+                    self.compile_exp(lhs, builder);
+                    self.compile_exp(rhs, builder);
+                    let op = BinaryOp::of(op).expect("non-logical operators are binary ops");
+                    builder.emit(Instr::Binary(op), span);
+                }
+            },
+            ExpKind::Call(name, exps) => {
+                for exp in &exps.exps {
+                    self.compile_exp(exp, builder);
+                }
+                // the Interpreter evaluates actuals before looking up the function.
+                // a name without a def may be a host function, which is only known when the vm runs
+                let instr = match self.funcs.get(name.as_str()) {
+                    None => {
+                        self.module.host_names.push(name.clone());
+                        Instr::CallHost {
+                            name: self.module.host_names.len() - 1,
+                            args: exps.exps.len(),
+                        }
+                    }
+                    Some(&(_, arity)) if arity != exps.exps.len() => self.fail(Failure::ArgMismatch {
+                        got: exps.exps.len(),
+                        expected: arity,
+                    }),
+                    Some(&(func, _)) => {
+                        self.module.call_sites.push(exp.clone());
+                        Instr::Call {
+                            func,
+                            site: self.module.call_sites.len() - 1,
+                        }
+                    }
+                };
+                builder.emit(instr, span);
+            }
+            ExpKind::BuiltIn(builtin) => {
+                let (func, exps) = BuiltinFn::of(&builtin.builtin);
+                // built-ins check their arity before evaluating any args
+                if func.arity() != exps.exps.len() {
+                    let instr = self.fail(Failure::ArgMismatch {
+                        got: exps.exps.len(),
+                        expected: func.arity(),
+                    });
+                    builder.emit(instr, span);
+                    return;
+                }
+                for exp in &exps.exps {
+                    self.compile_exp(exp, builder);
+                }
+                builder.emit(Instr::BuiltIn(func), span);
+            }
+            ExpKind::Paren(exp) => self.compile_exp(exp, builder),
+            ExpKind::Unary(unop, exp) => {
+                self.compile_exp(exp, builder);
+                builder.emit(Instr::Unary(unop.unop), span);
+            }
+            ExpKind::Array(exps) => {
+                for exp in &exps.exps {
+                    self.compile_exp(exp, builder);
+                }
+                builder.emit(Instr::Array(exps.exps.len()), span);
+            }
+            ExpKind::ArrayAccess { array, index } => {
+                self.compile_exp(array, builder);
+                self.compile_exp(index, builder);
+                builder.emit(Instr::Index, span);
+            }
+        }
+    }
+
+    // compiles lhs logical rhs into jumps, so that rhs is skipped when lhs decides the result:
+    // lhs, jump to decided if it decides, rhs, jump to decided if it decides, push the undecided result
+    fn compile_logical(
+        &mut self,
+        lhs: &Exp,
+        logical: &Logical,
+        rhs: &Exp,
+        span: Span,
+        builder: &mut FunctionBuilder,
+    ) {
+        // || is decided by a truthy operand, && by one that isn't
+        let (decides, decided): (fn(usize) -> Instr, bool) = match logical.logical {
+            LogicalKind::Or => (Instr::JumpIfTrue, true),
+            LogicalKind::And => (Instr::JumpIfFalse, false),
+        };
+
+        self.compile_exp(lhs, builder);
+        let lhs_decides = builder.emit(decides(0), span);
+        self.compile_exp(rhs, builder);
+        let rhs_decides = builder.emit(decides(0), span);
+
+        let undecided = self.boolean(!decided);
+        builder.emit(Instr::Const(undecided), span);
+        let to_end = builder.emit(Instr::Jump(0), span);
+
+        builder.patch(lhs_decides);
+        builder.patch(rhs_decides);
+        let decided = self.boolean(decided);
+        builder.emit(Instr::Const(decided), span);
+        builder.patch(to_end);
+    }
+
+    // returns the pool index of the number value, adding it if it isn't there yet
+    fn constant(&mut self, value: f64) -> usize {
+        self.intern(Constant::Num(value.to_bits()), || Value::from(value))
+    }
+
+    fn boolean(&mut self, value: bool) -> usize {
+        self.intern(Constant::Bool(value), || Value::from(value))
+    }
+
+    // returns the pool index of constant, adding the value it makes if it isn't there yet
+    fn intern(&mut self, constant: Constant, make: impl FnOnce() -> Value) -> usize {
+        let constants = &mut self.module.constants;
+        *self.constants.entry(constant).or_insert_with(|| {
+            constants.push(make());
+            constants.len() - 1
+        })
+    }
+
+    // returns the instruction loading the variable name, or the const with that name if there is no such variable.
+    // fails if neither is in scope
+    fn load(&mut self, name: &str, builder: &FunctionBuilder) -> Instr {
+        match builder.resolve(name) {
+            Some(slot) => Instr::Load(slot),
+            None => match self.globals.get(name) {
+                Some(&global) => Instr::LoadGlobal(global),
+                None => self.fail(Failure::UnboundVar(name.to_string())),
+            },
+        }
+    }
+
+    fn fail(&mut self, failure: Failure) -> Instr {
+        self.module.failures.push(failure);
+        Instr::Fail(self.module.failures.len() - 1)
+    }
+}
+
+impl FunctionBuilder {
+    fn new(args: &[String]) -> FunctionBuilder {
+        FunctionBuilder {
+            // args take the first slots, in order.
+            // when args share a name the last one is bound, like in the Interpreter
+            locals: args.to_vec(),
+            scopes: vec![args.iter().enumerate().map(|(slot, name)| (name.clone(), slot)).collect()],
+            code: Vec::new(),
+            spans: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    // appends instr, returning its index
+    fn emit(&mut self, instr: Instr, span: Span) -> usize {
+        self.code.push(instr);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    // points the jump at index to the next instruction emitted
+    fn patch(&mut self, jump: usize) {
+        self.patch_all(&[jump], self.code.len());
+    }
+
+    // points each of the jumps at target
+    fn patch_all(&mut self, jumps: &[usize], target: usize) {
+        for &jump in jumps {
+            match &mut self.code[jump] {
+                Instr::Jump(to) | Instr::JumpIfTrue(to) | Instr::JumpIfFalse(to) | Instr::Iterate(to) => {
+                    *to = target
+                }
+                other => unreachable!("can only patch jumps, found {:?}", other),
+            }
+        }
+    }
+
+    // the innermost loop being compiled, break and continue are only parsed inside one
+    fn innermost_loop(&mut self) -> &mut Loop {
+        self.loops.last_mut().expect("break and continue are inside a loop")
+    }
+
+    // adds a slot for a value the function keeps for itself, under a name no variable can have
+    fn hidden(&mut self) -> usize {
+        self.locals.push(format!("<hidden {}>", self.locals.len()));
+        self.locals.len() - 1
+    }
+
+    // returns the slot of the innermost variable in scope called name
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // adds a slot for a variable declared in the innermost scope
+    fn declare(&mut self, name: &str) -> usize {
+        self.locals.push(name.to_string());
+        let slot = self.locals.len() - 1;
+        self.scopes
+            .last_mut()
+            .expect("there is always a scope")
+            .insert(name.to_string(), slot);
+        slot
+    }
+}
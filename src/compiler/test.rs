@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::*;
+    use crate::ast::generate_ast;
+    use crate::scanner::Scanner;
+
+    fn compile_source(source: &str) -> Module {
+        let mut scan = Scanner::new(source.to_string()).unwrap();
+        compile(&generate_ast(&mut scan).unwrap())
+    }
+
+    #[test]
+    fn test_slots() {
+        let module = compile_source("def f(a, b) { let c := a + b; return c; } def main() { return f(1, 2); }");
+        let f = &module.functions[0];
+        assert_eq!(f.arity, 2);
+        assert_eq!(f.locals, vec!["a", "b", "c"]);
+        assert_eq!(
+            f.code,
+            vec![
+                Instr::Load(0),
+                Instr::Load(1),
+                Instr::Binary(BinaryOp::Add),
+                Instr::Store(2),
+                Instr::Load(2),
+                Instr::Return,
+                Instr::ReturnNone,
+            ]
+        );
+        assert_eq!(f.code.len(), f.spans.len());
+        assert_eq!(module.main, Some(1));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let module = compile_source(
+            "def main() { a := 1; if (a) { let a := 2; b := a; } return a + b; }",
+        );
+        let main = &module.functions[0];
+        // the inner a and b get slots of their own, which go out of scope with the block
+        assert_eq!(main.locals, vec!["a", "a", "b"]);
+        assert_eq!(main.code[1], Instr::StoreUndeclared(0));
+        assert_eq!(&main.code[5..9], &[Instr::Store(1), Instr::Load(1), Instr::StoreUndeclared(2), Instr::Load(0)]);
+        assert_eq!(main.code[9], Instr::Fail(0));
+        assert_eq!(module.failures, vec![Failure::UnboundVar(String::from("b"))]);
+    }
+
+    #[test]
+    fn test_constant_pool() {
+        let module = compile_source("def main() { a := 2; b := 2; return a * 3 + 2; }");
+        assert_eq!(module.constants, vec![Value::from(2f64), Value::from(3f64)]);
+    }
+
+    #[test]
+    fn test_later_def_wins() {
+        let module = compile_source("def f() { return 1; } def main() { return f(); } def f() { return 2; }");
+        assert_eq!(module.functions.len(), 2);
+        match module.functions[0].code[0] {
+            Instr::Const(constant) => assert_eq!(module.constants[constant], Value::from(2f64)),
+            other => panic!("expected Const, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_deferred_failures() {
+        let module = compile_source("def f(a) { return a; } def main() { g(); return f(); }");
+        assert_eq!(module.failures, vec![Failure::ArgMismatch { got: 0, expected: 1 }]);
+        assert!(module.functions[1].code.contains(&Instr::Fail(0)));
+        // g might be a host function, so it is looked up when it is called
+        assert_eq!(module.host_names, vec![String::from("g")]);
+        assert!(module.functions[1].code.contains(&Instr::CallHost { name: 0, args: 0 }));
+    }
+}
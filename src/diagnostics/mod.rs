@@ -0,0 +1,421 @@
+use std::fmt::Display;
+
+use crate::ast::{ASTError, Expected};
+use crate::check::CheckError;
+use crate::interpreter::errors::InterpreterError;
+use crate::interpreter::ValueDiscriminants;
+use crate::scanner::ScannerError;
+use crate::span::Span;
+use crate::tokens::Token;
+
+#[cfg(test)]
+mod test;
+
+// How serious a Diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+// A message attached to a region of source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+// Diagnostic is a problem found in an smp program, in any stage of running it.
+// the primary label marks where the problem is, secondary labels add related locations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    // sets the primary label, placeholder spans are ignored as they point nowhere
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        if !span.is_dummy() {
+            self.primary = Some(Label {
+                span,
+                message: message.into(),
+            });
+        }
+        self
+    }
+
+    // adds a secondary label, placeholder spans are ignored as they point nowhere
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        if !span.is_dummy() {
+            self.secondary.push(Label {
+                span,
+                message: message.into(),
+            });
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    // Renders this diagnostic against the source it was found in, like rustc does:
+    //
+    // error: unbound variable `b`
+    //  --> program.smp:4:25
+    //   |
+    // 4 |         return a + (2 * b);
+    //   |                         ^ not found in this scope
+    //   |
+    //   = note: ...
+    pub fn render(&self, path: &str, source: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity, self.message);
+
+        // primary label first, then secondary labels in source order
+        let mut labels: Vec<(&Label, char)> = Vec::new();
+        if let Some(primary) = &self.primary {
+            labels.push((primary, '^'));
+        }
+        let mut secondary: Vec<&Label> = self.secondary.iter().collect();
+        secondary.sort_by_key(|label| label.span.start);
+        labels.extend(secondary.into_iter().map(|label| (label, '-')));
+
+        let gutter = labels
+            .iter()
+            .map(|(label, _)| label.span.line.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let blank = format!("{} |", " ".repeat(gutter));
+
+        if let Some((first, _)) = labels.first() {
+            out.push_str(&format!(
+                "{}--> {}:{}:{}\n",
+                " ".repeat(gutter),
+                path,
+                first.span.line,
+                first.span.col
+            ));
+            out.push_str(&format!("{}\n", blank));
+        }
+
+        let lines: Vec<&str> = source.lines().collect();
+        let mut last_line = None;
+        for (label, marker) in &labels {
+            let line = lines
+                .get((label.span.line as usize).saturating_sub(1))
+                .copied()
+                .unwrap_or("");
+
+            // each source line is only shown once, with every label on it underneath
+            if last_line != Some(label.span.line) {
+                out.push_str(&format!("{:>width$} | {}\n", label.span.line, line, width = gutter));
+                last_line = Some(label.span.line);
+            }
+            out.push_str(&format!("{} {}\n", blank, underline(line, label, *marker)));
+        }
+
+        if !self.notes.is_empty() {
+            if !labels.is_empty() {
+                out.push_str(&format!("{}\n", blank));
+            }
+            for note in &self.notes {
+                out.push_str(&format!("{} = note: {}\n", " ".repeat(gutter), note));
+            }
+        }
+
+        out
+    }
+}
+
+impl From<CheckError> for Diagnostic {
+    fn from(err: CheckError) -> Self {
+        let span = err.span();
+        match err {
+            CheckError::UnboundFunc(name, _) => {
+                Diagnostic::error(format!("unbound function `{}`", name))
+                    .with_primary(span, "no function with this name")
+            }
+            CheckError::ArgMismatch { name, got, expected, .. } => Diagnostic::error(format!(
+                "`{}` takes {} argument(s), but {} were supplied",
+                name, expected, got
+            ))
+            .with_primary(span, format!("called with {} argument(s)", got)),
+            CheckError::UnboundVar(name, _) => {
+                Diagnostic::error(format!("unbound variable `{}`", name))
+                    .with_primary(span, "not assigned in this scope on any path")
+            }
+            CheckError::DuplicateDef { name, previous, .. } => {
+                Diagnostic::error(format!("`{}` is defined more than once", name))
+                    .with_primary(span, "redefined here")
+                    .with_secondary(previous, "previously defined here")
+                    .with_note("the last definition replaces the others")
+            }
+            CheckError::DuplicateParam { name, def, .. } => {
+                Diagnostic::error(format!("`{}` takes more than one parameter called `{}`", def, name))
+                    .with_primary(span, "duplicate parameter")
+            }
+            CheckError::MissingReturn { def, .. } => {
+                Diagnostic::error(format!("`{}` may end without returning a value", def))
+                    .with_primary(span, format!("`{}` can reach its end after this", def))
+                    .with_note("every function other than main must return a value")
+            }
+            CheckError::MissingMain => {
+                Diagnostic::error("no `main` function")
+                    .with_note("every program starts from a function called main")
+            }
+            CheckError::MainParams(count, _) => {
+                Diagnostic::warning(format!("`main` takes {} parameter(s)", count))
+                    .with_primary(span, "defined here")
+                    .with_note("it is called with the arguments given after the file name")
+            }
+        }
+    }
+}
+
+// builds the marker underline for label on line, followed by the label's message.
+// spans running past the end of the line are underlined up to the end of the line.
+fn underline(line: &str, label: &Label, marker: char) -> String {
+    // columns count characters, but span lengths count bytes, so widen the end out to a whole character
+    let col = (label.span.col as usize).saturating_sub(1);
+    let start = line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+    let end = (start + label.span.end.saturating_sub(label.span.start)).min(line.len());
+    let end = (end..=line.len()).find(|&i| line.is_char_boundary(i)).unwrap_or(line.len());
+    let len = line[start..end].chars().count().max(1);
+
+    // keep tabs from the source line so markers line up with it
+    let indent: String = line[..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let markers: String = std::iter::repeat_n(marker, len).collect();
+
+    if label.message.is_empty() {
+        format!("{}{}", indent, markers)
+    } else {
+        format!("{}{} {}", indent, markers, label.message)
+    }
+}
+
+// lists the expected alternatives in prose: "a", "a or b", "a, b or c"
+fn describe_expected(expected: &[Expected]) -> String {
+    let names: Vec<String> = expected.iter().map(|e| e.to_string()).collect();
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+// name of a value type as shown to users
+fn type_name(value_type: ValueDiscriminants) -> &'static str {
+    match value_type {
+        ValueDiscriminants::Num => "number",
+        ValueDiscriminants::Array => "array",
+        ValueDiscriminants::Str => "string",
+        ValueDiscriminants::Bool => "boolean",
+    }
+}
+
+impl From<ScannerError> for Diagnostic {
+    fn from(err: ScannerError) -> Self {
+        match err {
+            ScannerError::UnexpectedToken(found, span) => {
+                Diagnostic::error(format!("unexpected character `{}`", found))
+                    .with_primary(span, "unexpected character")
+            }
+            ScannerError::UnexpectedEof(span) => {
+                Diagnostic::error("unexpected end of file").with_primary(span, "")
+            }
+            ScannerError::InvalidNumeric(num, span) => {
+                Diagnostic::error(format!("invalid number `{}`", num))
+                    .with_primary(span, "not a valid number")
+            }
+            ScannerError::UnterminatedString(span) => {
+                Diagnostic::error("unterminated string")
+                    .with_primary(span, "missing a closing `\"` on this line")
+            }
+            ScannerError::InvalidEscape(escape, span) => {
+                Diagnostic::error(format!("unknown escape `{}`", escape))
+                    .with_primary(span, "unknown escape sequence")
+                    .with_note("valid escapes are \\n, \\t, \\r, \\0, \\\\ and \\\"")
+            }
+        }
+    }
+}
+
+impl From<ASTError> for Diagnostic {
+    fn from(err: ASTError) -> Self {
+        match err {
+            ASTError::ScannerError(err) => Diagnostic::from(err),
+            ASTError::UnexpectedToken {
+                found,
+                expected,
+                span,
+            } => {
+                let found = match found {
+                    Token::Eof => String::from("end of file"),
+                    other => format!("`{}`", other),
+                };
+                if expected.is_empty() {
+                    Diagnostic::error(format!("unexpected {}", found))
+                        .with_primary(span, "unexpected token")
+                } else {
+                    let expected = describe_expected(&expected);
+                    Diagnostic::error(format!("expected {}, found {}", expected, found))
+                        .with_primary(span, format!("expected {}", expected))
+                }
+            }
+            ASTError::InvalidOperator { found, span } => {
+                Diagnostic::error(format!("`{}` is not an operator", found))
+                    .with_primary(span, "expected an operator")
+            }
+            ASTError::InvalidBuiltin { found, span } => {
+                Diagnostic::error(format!("`{}` is not a built-in function", found))
+                    .with_primary(span, "expected a built-in function")
+            }
+            ASTError::OutsideLoop { found, span } => {
+                Diagnostic::error(format!("`{}` outside of a loop", found))
+                    .with_primary(span, format!("cannot `{}` outside of a loop", found))
+            }
+            ASTError::ArraySizeLiteral { size, span } => {
+                Diagnostic::error(format!("`[{}]` no longer creates an array of zeros", size))
+                    .with_primary(span, format!("use `array({})` instead", size))
+                    .with_note(format!("for an array holding just {}, write `[({})]`", size, size))
+            }
+        }
+    }
+}
+
+impl From<InterpreterError> for Diagnostic {
+    fn from(err: InterpreterError) -> Self {
+        Diagnostic::from(&err)
+    }
+}
+
+impl From<&InterpreterError> for Diagnostic {
+    fn from(err: &InterpreterError) -> Self {
+        let span = err.span();
+        match err {
+            InterpreterError::UnboundVar(name, _) => {
+                Diagnostic::error(format!("unbound variable `{}`", name))
+                    .with_primary(span, "not found in this scope")
+            }
+            InterpreterError::UndeclaredVar(name, _) => {
+                Diagnostic::error(format!("assignment to undeclared variable `{}`", name))
+                    .with_primary(span, "not declared in this scope")
+                    .with_note(format!("declare it with `let {} := ...`", name))
+            }
+            InterpreterError::ConstAssign(name, _) => {
+                Diagnostic::error(format!("cannot assign to constant `{}`", name))
+                    .with_primary(span, "constants can't be changed")
+                    .with_note(format!("declare a local with `let {} := ...` to shadow it", name))
+            }
+            InterpreterError::UnboundFunc(name, _) => {
+                Diagnostic::error(format!("unbound function `{}`", name))
+                    .with_primary(span, "no function with this name")
+            }
+            InterpreterError::TypeError {
+                found_type,
+                expected_type,
+                ..
+            } => Diagnostic::error("mismatched types").with_primary(
+                span,
+                format!(
+                    "expected {}, found {}",
+                    type_name(*expected_type),
+                    type_name(*found_type)
+                ),
+            ),
+            InterpreterError::ArgMismatch { got, expected, .. } => {
+                Diagnostic::error(format!(
+                    "expected {} argument(s), but {} were supplied",
+                    expected, got
+                ))
+                .with_primary(span, format!("called with {} argument(s)", got))
+            }
+            InterpreterError::ValuelessExpression(_) => {
+                Diagnostic::error("expression has no value")
+                    .with_primary(span, "this call did not return a value")
+                    .with_note("every function other than main must return a value")
+            }
+            InterpreterError::StackOverflow { depth, function, .. } => {
+                Diagnostic::error(format!("stack overflow calling `{}`, {} calls deep", function, depth))
+                    .with_primary(span, "too many calls in progress")
+                    .with_note("a recursive function may be missing its base case")
+            }
+            InterpreterError::BudgetExhausted { budget, .. } => {
+                Diagnostic::error(format!("step budget of {} exhausted", budget))
+                    .with_primary(span, "evaluation stopped here")
+            }
+            InterpreterError::DeadlineExceeded(_) => {
+                Diagnostic::error("deadline exceeded").with_primary(span, "evaluation stopped here")
+            }
+            InterpreterError::DivideByZero(_) => {
+                Diagnostic::error("division by zero").with_primary(span, "divisor is zero")
+            }
+            InterpreterError::IndexOutOfBounds { index, len, .. } => Diagnostic::error(format!(
+                "index out of bounds: the length is {} but the index is {}",
+                len, index
+            ))
+            .with_primary(span, "index out of bounds"),
+            InterpreterError::NonIntegerIndex { index, .. } => {
+                Diagnostic::error(format!("array index `{}` is not a whole number", index))
+                    .with_primary(span, "expected a whole number")
+            }
+            InterpreterError::InvalidArraySize { size, .. } => {
+                Diagnostic::error(format!("invalid array size `{}`", size))
+                    .with_primary(span, "expected a whole number, at least 0")
+            }
+            InterpreterError::IOError(err, _) => {
+                Diagnostic::error(format!("failed to read input: {}", err))
+                    .with_primary(span, "while reading input here")
+            }
+            InterpreterError::OutputError(err, _) => {
+                Diagnostic::error(format!("failed to write output: {}", err))
+                    .with_primary(span, "while writing output here")
+            }
+            InterpreterError::InvalidInput(err, _) => {
+                Diagnostic::error(format!("invalid numeric input: {}", err))
+                    .with_primary(span, "could not be read as a number")
+            }
+            InterpreterError::InvalidChar(err, _) => {
+                Diagnostic::error(format!("invalid input: {}", err))
+                    .with_primary(span, "while reading input here")
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::*;
+    use crate::ast::generate_ast;
+    use crate::interpreter::Interpreter;
+    use crate::scanner::Scanner;
+
+    // runs source to completion, returning the diagnostic for the first error
+    fn diagnose(source: &str) -> Diagnostic {
+        let program = match Scanner::new(source.to_string())
+            .map_err(ASTError::from)
+            .and_then(|mut scanner| generate_ast(&mut scanner))
+        {
+            Ok(program) => program,
+            Err(err) => return Diagnostic::from(err),
+        };
+        match Interpreter::new(program).execute() {
+            Err(err) => Diagnostic::from(err),
+            Ok(value) => panic!("expected an error, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_render_runtime_error() {
+        let source = "def main() {\n    a := 1;\n    return a + (2 * b);\n}\n";
+        let rendered = diagnose(source).render("prog.smp", source);
+        assert_eq!(
+            rendered,
+            "error: unbound variable `b`\n\
+             \x20--> prog.smp:3:21\n\
+             \x20 |\n\
+             3 |     return a + (2 * b);\n\
+             \x20 |                     ^ not found in this scope\n"
+        );
+    }
+
+    #[test]
+    fn test_render_parse_error() {
+        let source = "def main() {\n    return 1\n}";
+        let diagnostic = diagnose(source);
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.message, "expected `;` or operator, found `}`");
+        assert_eq!(diagnostic.primary.as_ref().unwrap().span.line, 3);
+    }
+
+    #[test]
+    fn test_render_scanner_error() {
+        let source = "def main() {\n    return 1 $ 2;\n}";
+        let rendered = diagnose(source).render("prog.smp", source);
+        assert!(rendered.starts_with("error: unexpected character `$`\n"));
+        assert!(rendered.contains("2 |     return 1 $ 2;\n  |              ^ unexpected character\n"));
+    }
+
+    #[test]
+    fn test_render_labels_and_notes() {
+        let source = "def f() {\n    x := 1;\n}\n\ndef main() {\n    return f();\n}\n";
+        let diagnostic = diagnose(source)
+            .with_secondary(Span::new(0, 9, 1, 1), "defined here")
+            .with_note("add a return statement to f");
+        let rendered = diagnostic.render("prog.smp", source);
+        assert_eq!(
+            rendered,
+            "error: expression has no value\n\
+             \x20--> prog.smp:6:12\n\
+             \x20 |\n\
+             6 |     return f();\n\
+             \x20 |            ^^^ this call did not return a value\n\
+             1 | def f() {\n\
+             \x20 | --------- defined here\n\
+             \x20 |\n\
+             \x20 = note: every function other than main must return a value\n\
+             \x20 = note: add a return statement to f\n"
+        );
+    }
+
+    #[test]
+    fn test_unlocated_error() {
+        let source = "def notmain() {\n    return 1;\n}\n";
+        let rendered = diagnose(source).render("prog.smp", source);
+        assert_eq!(rendered, "error: unbound function `main`\n");
+    }
+
+    #[test]
+    fn test_render_index_error() {
+        let source = "def main() {\n    a := array(2);\n    return a[2];\n}\n";
+        let rendered = diagnose(source).render("prog.smp", source);
+        assert_eq!(
+            rendered,
+            "error: index out of bounds: the length is 2 but the index is 2\n\
+             \x20--> prog.smp:3:12\n\
+             \x20 |\n\
+             3 |     return a[2];\n\
+             \x20 |            ^^^^ index out of bounds\n"
+        );
+    }
+
+    #[test]
+    fn test_render_non_ascii() {
+        // columns count characters, as do markers
+        let source = "def main() {\n    return \"é\" + b;\n}\n";
+        let rendered = diagnose(source).render("prog.smp", source);
+        assert!(rendered.contains(" --> prog.smp:2:18\n"));
+        assert!(rendered.contains("2 |     return \"é\" + b;\n  |                  ^ not found in this scope\n"));
+
+        // a span ending inside a character underlines the whole of it
+        let source = "x := é;";
+        let rendered = Diagnostic::error("unexpected character")
+            .with_primary(Span::new(5, 6, 1, 6), "")
+            .render("prog.smp", source);
+        assert!(rendered.ends_with("1 | x := é;\n  |      ^\n"));
+    }
+}
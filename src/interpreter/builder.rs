@@ -0,0 +1,129 @@
+use std::{cell::{Cell, RefCell}, collections::HashSet, io, time::Instant};
+
+use super::defs::Defs;
+use super::host::{HostFnBody, HostFns};
+use super::input::Input;
+use super::output::Output;
+use super::symbols::Program;
+use super::{Environment, Interpreter, Value, MAIN, RECURSION_LIMIT};
+
+// InterpreterBuilder configures an Interpreter before it is built.
+// anything not set keeps the default: stdin and stdout, calling main without args,
+// the default recursion limit, no step budget or deadline, and nothing strict
+pub struct InterpreterBuilder {
+    program: Program,
+    input: Option<Input>,
+    output: Option<Output>,
+    entry: String,
+    args: Vec<Value>,
+    recursion_limit: usize,
+    step_budget: Option<u64>,
+    deadline: Option<Instant>,
+    strict_conditions: bool,
+    strict_declarations: bool,
+    hosts: HostFns,
+}
+
+impl InterpreterBuilder {
+    pub fn new(program: Program) -> InterpreterBuilder {
+        InterpreterBuilder {
+            program,
+            input: None,
+            output: None,
+            entry: MAIN.to_string(),
+            args: Vec::new(),
+            recursion_limit: RECURSION_LIMIT,
+            step_budget: None,
+            deadline: None,
+            strict_conditions: false,
+            strict_declarations: false,
+            hosts: HostFns::new(),
+        }
+    }
+
+    // Sets where input() reads lines from
+    pub fn input(mut self, input: Input) -> InterpreterBuilder {
+        self.input = Some(input);
+        self
+    }
+
+    // Sets where printed values and input() prompts go
+    pub fn output(mut self, output: Output) -> InterpreterBuilder {
+        self.output = Some(output);
+        self
+    }
+
+    // Sets the name of the def execute starts from, instead of main
+    pub fn entry(mut self, name: &str) -> InterpreterBuilder {
+        self.entry = name.to_string();
+        self
+    }
+
+    // Sets the args the entry def is called with, of which there must be as many as it takes
+    pub fn args(mut self, args: Vec<Value>) -> InterpreterBuilder {
+        self.args = args;
+        self
+    }
+
+    // Sets how many calls may be in progress at once, counting the entry def.
+    // a call past the limit raises a StackOverflow error, rather than overflowing the Rust stack.
+    // so does a call made when the thread evaluating is about to run out of stack, before the limit is reached
+    pub fn recursion_limit(mut self, limit: usize) -> InterpreterBuilder {
+        self.recursion_limit = limit;
+        self
+    }
+
+    // Sets how many statements and expressions may be evaluated, counting each one nested in another.
+    // evaluating any more raises a BudgetExhausted error
+    pub fn step_budget(mut self, budget: u64) -> InterpreterBuilder {
+        self.step_budget = Some(budget);
+        self
+    }
+
+    // Sets when evaluation must have finished by. it is checked at every call and each time a loop goes around,
+    // raising a DeadlineExceeded error once it has passed
+    pub fn deadline(mut self, deadline: Instant) -> InterpreterBuilder {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    // see Interpreter::with_strict_conditions
+    pub fn strict_conditions(mut self, strict: bool) -> InterpreterBuilder {
+        self.strict_conditions = strict;
+        self
+    }
+
+    // see Interpreter::with_strict_declarations
+    pub fn strict_declarations(mut self, strict: bool) -> InterpreterBuilder {
+        self.strict_declarations = strict;
+        self
+    }
+
+    // see Interpreter::register_fn
+    pub fn register_fn(mut self, name: &str, arity: usize, body: HostFnBody) -> InterpreterBuilder {
+        self.hosts.register(name, arity, body);
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        Interpreter {
+            program: self.program,
+            defs: Defs::new(),
+            const_names: HashSet::new(),
+            globals: Environment::new(),
+            hosts: self.hosts,
+            input: RefCell::new(self.input.unwrap_or_else(|| Input::from(io::stdin()))),
+            output: RefCell::new(self.output.unwrap_or_else(|| Output::from(io::stdout()))),
+            entry: self.entry,
+            args: self.args,
+            depth: Cell::new(0),
+            recursion_limit: self.recursion_limit,
+            steps: Cell::new(0),
+            step_budget: self.step_budget,
+            deadline: self.deadline,
+            trace: RefCell::new(Vec::new()),
+            strict_conditions: self.strict_conditions,
+            strict_declarations: self.strict_declarations,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, rc::Rc};
 
 use super::{InterpreterError, symbols::Def};
+use crate::span::Span;
 
 
 #[derive(Debug, Clone)]
@@ -22,10 +23,17 @@ impl Defs {
     pub fn get_func(&self, name: &str) -> Result<&Def, InterpreterError> {
         match self.funcs.get(name) {
             Some(val) => Ok(val),
-            None => Err(InterpreterError::UnboundFunc(name.to_string())),
+            None => Err(InterpreterError::UnboundFunc(name.to_string(), Span::default())),
         }
     }
 
+    // Returns every bound def, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = &Def> {
+        let mut funcs: Vec<&Def> = self.funcs.values().map(|def| def.as_ref()).collect();
+        funcs.sort_by(|a, b| a.name.cmp(&b.name));
+        funcs.into_iter()
+    }
+
 }
 
 impl Default for Defs {
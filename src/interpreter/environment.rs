@@ -1,38 +1,96 @@
 use super::InterpreterError;
-use std::{collections::HashMap, fmt::Display};
+use crate::span::Span;
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+// A shared, mutable array. every copy of an array Value refers to the same elements
+pub type Array = Rc<RefCell<Vec<Value>>>;
 
 // Value represents a value in an smp program.
+// arrays have reference semantics: assigning or passing one aliases it, so changes made through
+// any alias are visible through all of them. programs can't compare arrays, `==` on them is a type error,
+// but PartialEq, for comparing results from Rust, compares elements, not identity.
+// strings are immutable, so sharing them is never observable.
 #[derive(Debug, EnumDiscriminants, Clone, PartialEq, PartialOrd)]
 pub enum Value {
     Num(f64),
-    Array(Vec<f64>),
+    Array(Array),
+    Str(Rc<str>),
+    Bool(bool),
 }
 
 impl Value {
     // tries to convert a Value into an f64
     pub fn into_f64(self) -> Result<f64, InterpreterError> {
-        Ok(match self {
-            Value::Num(val) => val,
-            Value::Array(_) => {
-                return Err(InterpreterError::TypeError {
-                    found_type: ValueDiscriminants::Array,
-                    expected_type: ValueDiscriminants::Num,
-                })
-            }
-        })
-    }
-
-    // tries to convert a Value into an Vec<f64>
-    pub fn into_vec(self) -> Result<Vec<f64>, InterpreterError> {
-        Ok(match self {
-            Value::Array(val) => val,
-            Value::Num(_) => {
-                return Err(InterpreterError::TypeError {
-                    found_type: ValueDiscriminants::Num,
-                    expected_type: ValueDiscriminants::Array,
-                })
+        match self {
+            Value::Num(val) => Ok(val),
+            other => Err(other.type_error(ValueDiscriminants::Num)),
+        }
+    }
+
+    // tries to convert a Value into a shared Array
+    pub fn into_array(self) -> Result<Array, InterpreterError> {
+        match self {
+            Value::Array(val) => Ok(val),
+            other => Err(other.type_error(ValueDiscriminants::Array)),
+        }
+    }
+
+    // tries to convert a Value into a bool
+    pub fn into_bool(self) -> Result<bool, InterpreterError> {
+        match self {
+            Value::Bool(val) => Ok(val),
+            other => Err(other.type_error(ValueDiscriminants::Bool)),
+        }
+    }
+
+    // tries to convert a Value into a string
+    pub fn into_str(self) -> Result<Rc<str>, InterpreterError> {
+        match self {
+            Value::Str(val) => Ok(val),
+            other => Err(other.type_error(ValueDiscriminants::Str)),
+        }
+    }
+
+    // the error for using this Value where a value of expected_type was needed
+    pub fn type_error(&self, expected_type: ValueDiscriminants) -> InterpreterError {
+        InterpreterError::TypeError {
+            found_type: ValueDiscriminants::from(self),
+            expected_type,
+            span: Span::default(),
+        }
+    }
+
+    // writes this Value for program output.
+    // arrays in seen are already being written, they are shown as [...] so that cycles end
+    fn write(&self, f: &mut std::fmt::Formatter<'_>, seen: &mut Vec<*const RefCell<Vec<Value>>>) -> std::fmt::Result {
+        match self {
+            Value::Num(val) => write!(f, "{}", val),
+            Value::Str(val) => write!(f, "{}", val),
+            Value::Bool(val) => write!(f, "{}", val),
+            Value::Array(val) => {
+                if seen.contains(&Rc::as_ptr(val)) {
+                    return write!(f, "[...]");
+                }
+                seen.push(Rc::as_ptr(val));
+
+                write!(f, "[")?;
+                for (i, element) in val.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    // elements are shown like literals
+                    match element {
+                        Value::Num(num) => write!(f, "{:?}", num)?,
+                        Value::Str(str) => write!(f, "{:?}", str)?,
+                        other => other.write(f, seen)?,
+                    }
+                }
+                write!(f, "]")?;
+
+                seen.pop();
+                Ok(())
             }
-        })
+        }
     }
 }
 
@@ -43,46 +101,121 @@ impl From<f64> for Value {
     }
 }
 
-// Creates a Value from a Vec<f64>
+// Creates a Value from a bool
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Value::Bool(val)
+    }
+}
+
+// Creates a Value from a Vec<f64>, as a new array of numbers
 impl From<Vec<f64>> for Value {
     fn from(val: Vec<f64>) -> Self {
-        Value::Array(val)
+        Value::from(val.into_iter().map(Value::from).collect::<Vec<Value>>())
+    }
+}
+
+// Creates a Value from a Vec<Value>, as a new array
+impl From<Vec<Value>> for Value {
+    fn from(val: Vec<Value>) -> Self {
+        Value::Array(Rc::new(RefCell::new(val)))
+    }
+}
+
+// Creates a Value from a str
+impl From<&str> for Value {
+    fn from(val: &str) -> Self {
+        Value::Str(Rc::from(val))
+    }
+}
+
+// Creates a Value from a String
+impl From<String> for Value {
+    fn from(val: String) -> Self {
+        Value::Str(Rc::from(val))
     }
 }
 
 // Display for Value, used for program output
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Num(val) => write!(f, "{}", val),
-            Value::Array(val) => write!(f, "{:?}", val),
-        }
+        self.write(f, &mut Vec::new())
     }
 }
 
-// Environment holds variable bindings
+// Environment holds variable bindings, as a chain of frames: one for each block being evaluated, innermost last.
+// variables declared in a frame go away with it
 #[derive(Debug)]
 pub struct Environment {
-    vars: HashMap<String, Value>,
+    frames: Vec<HashMap<String, Value>>,
 }
 
 impl Environment {
     pub fn new() -> Environment {
         Environment {
-            vars: HashMap::new(),
+            frames: vec![HashMap::new()],
         }
     }
 
-    // Binds a value to a name
-    pub fn bind_var(&mut self, name: String, value: Value) {
-        self.vars.insert(name, value);
+    // Starts a new innermost frame, for a block being entered
+    pub fn push_frame(&mut self) {
+        self.frames.push(HashMap::new());
+    }
+
+    // Ends the innermost frame, dropping the variables declared in it
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+        assert!(!self.frames.is_empty(), "the outermost frame is never popped");
+    }
+
+    // Declares a variable in the innermost frame, shadowing any outer variable with the same name
+    pub fn declare_var(&mut self, name: String, value: Value) {
+        self.innermost().insert(name, value);
+    }
+
+    // Assigns a value to the innermost variable with a name, which must already be declared
+    pub fn assign_var(&mut self, name: &str, value: Value) -> Result<(), InterpreterError> {
+        match self.frames.iter_mut().rev().find_map(|frame| frame.get_mut(name)) {
+            Some(var) => {
+                *var = value;
+                Ok(())
+            }
+            None => Err(InterpreterError::UndeclaredVar(name.to_string(), Span::default())),
+        }
+    }
+
+    // Returns true if a variable with name is declared in any frame
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.frames.iter().any(|frame| frame.contains_key(name))
+    }
+
+    // Returns every binding in scope, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        // inner frames shadow outer ones
+        let mut vars: HashMap<&String, &Value> = HashMap::new();
+        for frame in &self.frames {
+            vars.extend(frame.iter());
+        }
+        let mut vars: Vec<(&String, &Value)> = vars.into_iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        vars.into_iter()
     }
 
     // Retrieves the value bound to a name
     pub fn get_var(&self, name: &str) -> Result<Value, InterpreterError> {
-        match self.vars.get(name) {
+        match self.frames.iter().rev().find_map(|frame| frame.get(name)) {
             Some(val) => Ok(val.clone()),
-            None => Err(InterpreterError::UnboundVar(name.to_string())),
+            None => Err(InterpreterError::UnboundVar(name.to_string(), Span::default())),
         }
     }
+
+    fn innermost(&mut self) -> &mut HashMap<String, Value> {
+        self.frames.last_mut().expect("there is always a frame")
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -1,41 +1,237 @@
-
+use std::fmt;
 use std::string::FromUtf8Error;
 
+use crate::diagnostics::Diagnostic;
+
 use super::*;
 
+// Errors raised while evaluating a program.
+// errors raised outside of the evaluator (e.g. by Value conversions) start with a placeholder span,
+// which is filled in by the innermost expression or statement they pass through.
 #[derive(Debug)]
 pub enum InterpreterError {
-    UnboundVar(String),
-    UnboundFunc(String),
+    UnboundVar(String, Span),
+    // assignment to a variable that was never declared, under strict declarations
+    UndeclaredVar(String, Span),
+    // assignment to a global const, or a second const with the same name
+    ConstAssign(String, Span),
+    UnboundFunc(String, Span),
     TypeError {
         found_type: ValueDiscriminants,
         expected_type: ValueDiscriminants,
+        span: Span,
     },
     ArgMismatch {
         got: usize,
         expected: usize,
+        span: Span,
     },
     ValuelessExpression(Exp),
-    DivideByZero,
-    IOError(io::Error),
-    InvalidInput(ParseFloatError),
-    InvalidChar(FromUtf8Error),
+    // a call that would have made depth calls in progress at once, counting itself, more than the recursion limit allows.
+    // the Interpreter also raises it when the Rust stack is about to run out, which can be before the limit
+    StackOverflow {
+        depth: usize,
+        function: String,
+        span: Span,
+    },
+    // more steps were evaluated than the budget allows
+    BudgetExhausted {
+        budget: u64,
+        span: Span,
+    },
+    // evaluation was still going when the deadline passed
+    DeadlineExceeded(Span),
+    DivideByZero(Span),
+    // index is a whole number, but not in 0..len
+    IndexOutOfBounds {
+        index: f64,
+        len: usize,
+        span: Span,
+    },
+    NonIntegerIndex {
+        index: f64,
+        span: Span,
+    },
+    // size is negative, not a whole number, or too large to allocate
+    InvalidArraySize {
+        size: f64,
+        span: Span,
+    },
+    // input couldn't be read
+    IOError(io::Error, Span),
+    // a printed value or the input prompt couldn't be written
+    OutputError(io::Error, Span),
+    InvalidInput(ParseFloatError, Span),
+    InvalidChar(FromUtf8Error, Span),
+}
+
+impl InterpreterError {
+    // the source location this error was raised at
+    pub fn span(&self) -> Span {
+        match self {
+            InterpreterError::ValuelessExpression(exp) => exp.span,
+            InterpreterError::UnboundVar(_, span)
+            | InterpreterError::UndeclaredVar(_, span)
+            | InterpreterError::ConstAssign(_, span)
+            | InterpreterError::UnboundFunc(_, span)
+            | InterpreterError::TypeError { span, .. }
+            | InterpreterError::ArgMismatch { span, .. }
+            | InterpreterError::StackOverflow { span, .. }
+            | InterpreterError::BudgetExhausted { span, .. }
+            | InterpreterError::DeadlineExceeded(span)
+            | InterpreterError::DivideByZero(span)
+            | InterpreterError::IndexOutOfBounds { span, .. }
+            | InterpreterError::NonIntegerIndex { span, .. }
+            | InterpreterError::InvalidArraySize { span, .. }
+            | InterpreterError::IOError(_, span)
+            | InterpreterError::OutputError(_, span)
+            | InterpreterError::InvalidInput(_, span)
+            | InterpreterError::InvalidChar(_, span) => *span,
+        }
+    }
+
+    // attaches span to this error if it was raised without a location
+    pub fn with_span(mut self, at: Span) -> Self {
+        match &mut self {
+            InterpreterError::ValuelessExpression(_) => {}
+            InterpreterError::UnboundVar(_, span)
+            | InterpreterError::UndeclaredVar(_, span)
+            | InterpreterError::ConstAssign(_, span)
+            | InterpreterError::UnboundFunc(_, span)
+            | InterpreterError::TypeError { span, .. }
+            | InterpreterError::ArgMismatch { span, .. }
+            | InterpreterError::StackOverflow { span, .. }
+            | InterpreterError::BudgetExhausted { span, .. }
+            | InterpreterError::DeadlineExceeded(span)
+            | InterpreterError::DivideByZero(span)
+            | InterpreterError::IndexOutOfBounds { span, .. }
+            | InterpreterError::NonIntegerIndex { span, .. }
+            | InterpreterError::InvalidArraySize { span, .. }
+            | InterpreterError::IOError(_, span)
+            | InterpreterError::OutputError(_, span)
+            | InterpreterError::InvalidInput(_, span)
+            | InterpreterError::InvalidChar(_, span) => {
+                if span.is_dummy() {
+                    *span = at;
+                }
+            }
+        }
+        self
+    }
+
+    // an error writing output, which would otherwise be taken for one reading input
+    pub fn output(e: io::Error) -> Self {
+        InterpreterError::OutputError(e, Span::default())
+    }
 }
 
 impl From<io::Error> for InterpreterError {
     fn from(e: io::Error) -> Self {
-        InterpreterError::IOError(e)
+        InterpreterError::IOError(e, Span::default())
     }
 }
 
 impl From<ParseFloatError> for InterpreterError {
     fn from(e: ParseFloatError) -> Self {
-        InterpreterError::InvalidInput(e)
+        InterpreterError::InvalidInput(e, Span::default())
     }
 }
 
 impl From<std::string::FromUtf8Error> for InterpreterError {
     fn from(e: FromUtf8Error) -> Self {
-        InterpreterError::InvalidChar(e)
+        InterpreterError::InvalidChar(e, Span::default())
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Diagnostic::from(self).message)
+    }
+}
+
+// TraceFrame is a call that was in progress when an error was raised
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    // the call expression, a placeholder for the entry def which nothing called
+    pub call_site: Span,
+}
+
+// Traceback is an error along with the calls it was raised in, outermost first
+#[derive(Debug)]
+pub struct Traceback {
+    pub error: InterpreterError,
+    pub frames: Vec<TraceFrame>,
+}
+
+// how many times in a row the same frame is shown before the rest of its repeats are collapsed, as in Python
+const REPEATS_SHOWN: usize = 3;
+
+impl Traceback {
+    // the function of each frame, along with where in it evaluation was when the error was raised:
+    // the call into the next frame, or the error itself for the innermost frame
+    fn positions(&self) -> impl Iterator<Item = (&str, Span)> {
+        let inner = self.frames.iter().skip(1).map(|frame| frame.call_site);
+        let positions = inner.chain(std::iter::once(self.error.span()));
+        self.frames.iter().map(|frame| frame.function.as_str()).zip(positions)
+    }
+
+    // a line for each frame, described by describe, where a frame repeated by runaway recursion
+    // is only shown a few times followed by how many more times it was repeated
+    fn frame_lines(&self, describe: impl Fn(&str, Span) -> String) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut positions = self.positions().peekable();
+        while let Some((function, span)) = positions.next() {
+            let mut repeats = 1;
+            while positions.peek() == Some(&(function, span)) {
+                positions.next();
+                repeats += 1;
+            }
+            for _ in 0..repeats.min(REPEATS_SHOWN) {
+                lines.push(describe(function, span));
+            }
+            if repeats > REPEATS_SHOWN {
+                lines.push(format!("[Previous line repeated {} more times]", repeats - REPEATS_SHOWN));
+            }
+        }
+        lines
+    }
+
+    // Renders this traceback like Python does, followed by the error rendered against the source at path
+    pub fn render(&self, path: &str, source: &str) -> String {
+        let mut out = String::new();
+        if !self.frames.is_empty() {
+            out.push_str("Traceback (most recent call last):\n");
+            let describe = |function: &str, span| format!("File \"{}\", {}", path, describe_frame(function, span));
+            for line in self.frame_lines(describe) {
+                out.push_str(&format!("  {}\n", line));
+            }
+        }
+        out.push_str(&Diagnostic::from(&self.error).render(path, source));
+        out
+    }
+}
+
+impl fmt::Display for Traceback {
+    // Traceback (most recent call last):
+    //   line 6, col 12, in main
+    //   line 2, col 10, in f
+    // error: unbound variable `b`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.frames.is_empty() {
+            writeln!(f, "Traceback (most recent call last):")?;
+            for line in self.frame_lines(describe_frame) {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+        write!(f, "error: {}", self.error)
+    }
+}
+
+// describes where in function a frame was, leaving out the location if it isn't known
+fn describe_frame(function: &str, span: Span) -> String {
+    if span.is_dummy() {
+        return format!("in {}", function);
+    }
+    format!("line {}, col {}, in {}", span.line, span.col, function)
+}
@@ -5,11 +5,6 @@ pub fn truthy(value: f64) -> bool {
     value.abs() > EPSILON
 }
 
-// converts a boolean to a float
-pub fn bool_to_float(bool: bool) -> f64 {
-    (bool as u32) as f64
-}
-
 // Attempts to get the value of an expression that may not return a value.
 // if no value can be unwrapped, returns a ValuelessExpression interpreter error
 pub fn get_expression_result_value(
@@ -21,3 +16,13 @@ pub fn get_expression_result_value(
         None => Err(InterpreterError::ValuelessExpression(exp.clone())),
     }
 }
+
+// the Rust stack left over that a call needs to evaluate its body, up to the next call it makes.
+// in debug builds this is enough for a call nested a couple of hundred blocks deep
+const STACK_RED_ZONE: usize = 1024 * 1024;
+
+// Whether the Rust stack is too close to running out for another call.
+// on platforms where the stack can't be measured, only the recursion limit stops recursion
+pub fn stack_exhausted() -> bool {
+    matches!(stacker::remaining_stack(), Some(remaining) if remaining < STACK_RED_ZONE)
+}
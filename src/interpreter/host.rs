@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{errors::InterpreterError, ops, Value};
+
+// the body of a host function, given the values of the call's args
+pub type HostFnBody = Box<dyn Fn(&[Value]) -> Result<Value, InterpreterError>>;
+
+// HostFn is a Rust function the embedder exposes to scripts, called like a def
+pub struct HostFn {
+    pub arity: usize,
+    body: HostFnBody,
+}
+
+impl HostFn {
+    // Calls this function with args, checking their number like a def's
+    pub fn call(&self, args: &[Value]) -> Result<Value, InterpreterError> {
+        ops::check_arity(self.arity, args.len())?;
+        (self.body)(args)
+    }
+}
+
+impl fmt::Debug for HostFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostFn").field("arity", &self.arity).finish_non_exhaustive()
+    }
+}
+
+// HostFns are the host functions registered by name
+#[derive(Debug, Default)]
+pub struct HostFns {
+    funcs: HashMap<String, HostFn>,
+}
+
+impl HostFns {
+    pub fn new() -> HostFns {
+        HostFns {
+            funcs: HashMap::new(),
+        }
+    }
+
+    // Registers body as the host function name, replacing any registered with the same name
+    pub fn register(&mut self, name: &str, arity: usize, body: HostFnBody) {
+        self.funcs.insert(name.to_string(), HostFn { arity, body });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HostFn> {
+        self.funcs.get(name)
+    }
+}
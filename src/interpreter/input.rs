@@ -1,9 +1,11 @@
-use std::{borrow::BorrowMut, io::{self, BufRead, Stdin}};
+use std::{borrow::BorrowMut, cell::RefCell, io::{self, BufRead, Stdin}, rc::Rc};
 
 
 pub enum InputKind {
     Cursor(Vec<io::Cursor<String>>),
     Stdin(Stdin),
+    // a reader also read from elsewhere, e.g. by the REPL between entries
+    Shared(Rc<RefCell<dyn BufRead>>),
 }
 
 pub struct Input {
@@ -26,12 +28,21 @@ impl From<Vec<io::Cursor<String>>> for Input {
     }
 }
 
+impl From<Rc<RefCell<dyn BufRead>>> for Input {
+    fn from(r: Rc<RefCell<dyn BufRead>>) -> Self {
+        Input {
+            reader: InputKind::Shared(r)
+        }
+    }
+}
+
 impl Input {
 
     pub fn read_line(&mut self, buf: &mut String) -> Result<usize, io::Error>  {
         match self.reader.borrow_mut() {
             InputKind::Cursor(c) => c.pop().unwrap().read_line(buf),
             InputKind::Stdin(s) => s.read_line(buf),
+            InputKind::Shared(r) => RefCell::borrow_mut(r).read_line(buf),
         }
     }
 }
\ No newline at end of file
@@ -1,62 +1,185 @@
 pub mod defs;
 pub mod environment;
 pub mod symbols;
-mod helpers;
+pub(crate) mod helpers;
+pub mod ops;
 pub mod errors;
 pub mod input;
+pub mod output;
+pub mod builder;
+pub mod host;
 
 #[cfg(test)]
 mod test;
 
 use defs::Defs;
 pub use environment::{Environment, Value, ValueDiscriminants};
-use std::{cell::RefCell, io::{self, stdin, Cursor}, num::ParseFloatError, rc::Rc, usize};
+use std::{cell::{Cell, RefCell}, collections::HashSet, io::{self, Cursor}, num::ParseFloatError, rc::Rc, time::Instant};
+use ops::{BinaryOp, BuiltinFn};
 use symbols::*;
 use errors::*;
 use input::Input;
+use output::Output;
+use host::{HostFnBody, HostFns};
+use builder::InterpreterBuilder;
+use crate::span::Span;
 
 // main function name
-const MAIN: &str = "main";
+pub(crate) const MAIN: &str = "main";
+// how many calls may be in progress at once by default, see InterpreterBuilder::recursion_limit
+pub const RECURSION_LIMIT: usize = 1000;
 // Approximation for 0
 const EPSILON: f64 = 0.0000001;
 
 
+// Flow is where evaluation goes after a statement or block
+#[derive(Debug)]
+enum Flow {
+    // on to the next statement
+    Next,
+    Return(Value),
+    // out of the innermost loop
+    Break,
+    // on to the next iteration of the innermost loop
+    Continue,
+}
+
+impl Flow {
+    // the value returned, if any.
+    // break and continue only appear inside loops, so they never leave a def
+    fn into_value(self) -> Option<Value> {
+        match self {
+            Flow::Return(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
 // Interpreter evaluates a program Symbol (AST).
 pub struct Interpreter {
     program: Program,
     defs: Defs,
-    input: RefCell<input::Input>
+    // the names of every const, and the values of those evaluated so far
+    const_names: HashSet<String>,
+    globals: Environment,
+    // functions registered by the embedder, called when no def has their name
+    hosts: HostFns,
+    input: RefCell<input::Input>,
+    output: RefCell<Output>,
+    // the def execute starts from, and the args it is called with
+    entry: String,
+    args: Vec<Value>,
+    // calls in progress, which can't go past the limit
+    depth: Cell<usize>,
+    recursion_limit: usize,
+    // statements and expressions evaluated so far, which can't go past the budget
+    steps: Cell<u64>,
+    step_budget: Option<u64>,
+    // checked at each call and each time a loop goes around
+    deadline: Option<Instant>,
+    // the calls an error has been raised out of, innermost first
+    trace: RefCell<Vec<TraceFrame>>,
+    // conditions must be booleans, rather than anything with truthiness
+    strict_conditions: bool,
+    // variables must be declared with let before they are assigned
+    strict_declarations: bool,
 }
 
 
 impl Interpreter {
 
     pub fn new(program: Program) -> Interpreter {
-        Interpreter {
-            program: program,
-            defs: Defs::new(),
-            input: RefCell::new(Input::from(stdin())),
-        }
+        Interpreter::builder(program).build()
     }
 
     pub fn new_cursored(program: Program, input: Vec<Cursor<String>>) -> Interpreter {
-        Interpreter {
-            program,
-            defs: Defs::new(),
-            input: RefCell::new(Input::from(input))
-        }
+        Interpreter::builder(program).input(Input::from(input)).build()
+    }
+
+    // Creates an Interpreter reading input() from input, and printing to output
+    pub fn new_with_io(program: Program, input: Input, output: Output) -> Interpreter {
+        Interpreter::builder(program).input(input).output(output).build()
+    }
+
+    // Returns a builder to configure an Interpreter for program
+    pub fn builder(program: Program) -> InterpreterBuilder {
+        InterpreterBuilder::new(program)
+    }
+
+    // In strict mode, a condition of an if, while, logical operator or ! that isn't a boolean
+    // raises a TypeError, instead of numbers counting as true unless they are 0
+    pub fn with_strict_conditions(mut self, strict: bool) -> Interpreter {
+        self.strict_conditions = strict;
+        self
+    }
+
+    // In strict mode, assigning to a variable that isn't declared raises an UndeclaredVar error,
+    // instead of declaring it in the innermost block
+    pub fn with_strict_declarations(mut self, strict: bool) -> Interpreter {
+        self.strict_declarations = strict;
+        self
+    }
+
+    // Exposes the Rust function body to scripts as name, taking arity args.
+    // it is called like a def, but a def with the same name takes precedence.
+    // names of built-ins can't be registered, since they are keywords
+    pub fn register_fn(&mut self, name: &str, arity: usize, body: HostFnBody) {
+        self.hosts.register(name, arity, body);
+    }
+
+    // Executes this interpreters Program, starting from its entry def (main unless configured otherwise)
+    pub fn execute(self) -> Result<Option<Value>, InterpreterError> {
+        self.execute_traced().map_err(|traceback| traceback.error)
+    }
+
+    // Executes this interpreters Program like execute, returning any error along with the calls it was raised in
+    pub fn execute_traced(mut self) -> Result<Option<Value>, Traceback> {
+        self.run()
+    }
+
+    // Executes this interpreters Program like execute, also returning how many steps it took
+    pub fn execute_counting_steps(mut self) -> Result<(Option<Value>, u64), InterpreterError> {
+        let value = self.run().map_err(|traceback| traceback.error)?;
+        Ok((value, self.steps.get()))
     }
 
-    // Executes this interpreters Program
-    pub fn execute(mut self) -> Result<Option<Value>, InterpreterError> {
-        // evaluate defs in program
-        let mut env = Environment::new();
+    // Executes this interpreters Program like execute, calling the entry def with args
+    pub fn execute_with_args(self, args: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        self.execute_with_args_traced(args).map_err(|traceback| traceback.error)
+    }
+
+    // Executes this interpreters Program like execute_with_args, returning any error along with the calls it was raised in
+    pub fn execute_with_args_traced(mut self, args: &[Value]) -> Result<Option<Value>, Traceback> {
+        self.args = args.to_vec();
+        self.run()
+    }
+
+    fn run(&mut self) -> Result<Option<Value>, Traceback> {
+        let result = self.run_entry();
+        self.traced(result)
+    }
 
+    // Attaches the calls an error was raised in, which are cleared for the next evaluation
+    fn traced<T>(&self, result: Result<T, InterpreterError>) -> Result<T, Traceback> {
+        let frames = self.trace.take();
+        result.map_err(|error| Traceback {
+            error,
+            frames: frames.into_iter().rev().collect(),
+        })
+    }
+
+    fn run_entry(&mut self) -> Result<Option<Value>, InterpreterError> {
         // evaluate all defs
         self.eval_program();
 
-        // execute main
-        self.eval_call(&MAIN.to_string(), &Exps { exps: Vec::new() }, &mut env)
+        // evaluate consts, in order, before main
+        for c in std::mem::take(&mut self.program.consts) {
+            self.eval_const(&c)?;
+        }
+
+        // execute the entry def
+        let args = std::mem::take(&mut self.args);
+        self.call(&self.entry, args, Span::default())
     }
 
     // Evaluates all the top-level defs in the program
@@ -64,6 +187,54 @@ impl Interpreter {
         for def in &self.program.defs {
             self.defs.bind_func(def.name.clone(), Rc::new(def.clone()));
         }
+        // consts can't be assigned even before they are evaluated
+        for c in &self.program.consts {
+            self.const_names.insert(c.name.clone());
+        }
+    }
+
+    // Evaluates c and binds it as a global const, visible to every def.
+    // raises a ConstAssign error if a const with the same name was already bound
+    pub fn bind_const(&mut self, c: &Const) -> Result<(), Traceback> {
+        let result = self.eval_const(c);
+        self.traced(result)
+    }
+
+    fn eval_const(&mut self, c: &Const) -> Result<(), InterpreterError> {
+        let value = self.eval_exp(&c.exp, &mut Environment::new())?;
+        if self.globals.is_declared(&c.name) {
+            return Err(InterpreterError::ConstAssign(c.name.clone(), c.span));
+        }
+        self.const_names.insert(c.name.clone());
+        self.globals.declare_var(c.name.clone(), value);
+        Ok(())
+    }
+
+    // Returns all consts bound so far
+    pub fn consts(&self) -> &Environment {
+        &self.globals
+    }
+
+    // Binds def outside of the program, replacing any def with the same name.
+    // used to build up defs interactively
+    pub fn bind_def(&mut self, def: Def) {
+        self.defs.bind_func(def.name.clone(), Rc::new(def));
+    }
+
+    // Returns all defs bound so far
+    pub fn defs(&self) -> &Defs {
+        &self.defs
+    }
+
+    // Evaluates a statement outside of any def, in the given Environment.
+    // returns the value it returned, if any
+    pub fn eval_top_level(
+        &self,
+        statement: &Statement,
+        env: &mut Environment,
+    ) -> Result<Option<Value>, Traceback> {
+        let result = self.eval_statement(statement, env);
+        Ok(self.traced(result)?.into_value())
     }
 
     // Evaluates a function call to name with given actual args (exps) in the given environment
@@ -71,6 +242,7 @@ impl Interpreter {
         &self,
         name: &str,
         exps: &Exps,
+        site: Span,
         env: &mut Environment,
     ) -> Result<Option<Value>, InterpreterError> {
         // compute arg actuals
@@ -78,58 +250,113 @@ impl Interpreter {
         for exp in &exps.exps {
             actuals.push(self.eval_exp(exp, env)?);
         }
+        self.call(name, actuals, site)
+    }
 
-        // get function
-        let func = self.defs.get_func(&name.to_string())?;
+    // Calls the function name with the given actual args, from the call expression at site
+    fn call(&self, name: &str, actuals: Vec<Value>, site: Span) -> Result<Option<Value>, InterpreterError> {
+        // get function, falling back to a host function
+        let func = match (self.defs.get_func(name), self.hosts.get(name)) {
+            (Err(_), Some(host)) => return host.call(&actuals).map(Some),
+            (func, _) => func?,
+        };
 
         // ensure num actuals matches num args
-        if actuals.len() != func.args.names.len() {
-            return Err(InterpreterError::ArgMismatch {
-                got: actuals.len(),
-                expected: func.args.names.len(),
+        ops::check_arity(func.args.names.len(), actuals.len())?;
+
+        self.check_deadline()?;
+
+        // each call goes deeper into the Rust stack, runaway recursion is stopped before it runs out,
+        // at the recursion limit or sooner if the thread evaluating has less stack than the limit needs
+        let depth = self.depth.get() + 1;
+        if depth > self.recursion_limit || helpers::stack_exhausted() {
+            return Err(InterpreterError::StackOverflow {
+                depth,
+                function: name.to_string(),
+                span: Span::default(),
             });
         }
 
         // create a new environment with args bound to actuals
         let mut func_env = Environment::new();
         for (i, actual) in actuals.iter().enumerate() {
-            func_env.bind_var(func.args.names[i].clone(), actual.clone());
+            func_env.declare_var(func.args.names[i].clone(), actual.clone());
         }
 
         // evaluate func block under new environment
-        self.eval_block(&func.block, &mut func_env)
+        self.depth.set(depth);
+        let flow = self.eval_block(&func.block, &mut func_env);
+        self.depth.set(depth - 1);
+        if flow.is_err() {
+            self.trace.borrow_mut().push(TraceFrame {
+                function: name.to_string(),
+                call_site: site,
+            });
+        }
+        Ok(flow?.into_value())
+    }
+
+    // Retrieves the value of a variable in env, or of the const with that name if there is no such variable
+    fn get_var(&self, name: &str, env: &Environment) -> Result<Value, InterpreterError> {
+        env.get_var(name).or_else(|err| self.globals.get_var(name).map_err(|_| err))
+    }
+
+    // Counts a step of evaluation, failing once there have been more than the step budget allows
+    fn step(&self) -> Result<(), InterpreterError> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        match self.step_budget {
+            Some(budget) if steps > budget => Err(InterpreterError::BudgetExhausted {
+                budget,
+                span: Span::default(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Fails if the deadline has passed
+    fn check_deadline(&self) -> Result<(), InterpreterError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(InterpreterError::DeadlineExceeded(Span::default())),
+            _ => Ok(()),
+        }
     }
 
     // Evaluates the given expression in the given Environment
     fn eval_exp(&self, exp: &Exp, env: &mut Environment) -> Result<Value, InterpreterError> {
-        let res = match &*exp.exp {
-            ExpKind::Name(name) => env.get_var(&name),
+        // errors raised without a location happened in this expression
+        self.step()
+            .and_then(|_| self.eval_exp_kind(exp, env))
+            .map_err(|err| err.with_span(exp.span))
+    }
+
+    fn eval_exp_kind(&self, exp: &Exp, env: &mut Environment) -> Result<Value, InterpreterError> {
+        match &*exp.exp {
+            ExpKind::Name(name) => self.get_var(name, env),
             ExpKind::Num(value) => Ok(Value::from(*value)),
+            ExpKind::Str(value) => Ok(Value::from(value.as_str())),
+            ExpKind::Bool(value) => Ok(Value::from(*value)),
             ExpKind::Infix(lhs, op, rhs) => self.eval_infix(lhs, op, rhs, env),
             ExpKind::Call(name, exps) => {
-                helpers::get_expression_result_value(&exp, self.eval_call(name, exps, env))
+                helpers::get_expression_result_value(exp, self.eval_call(name, exps, exp.span, env))
             }
             ExpKind::BuiltIn(builtin) => {
                 self.eval_builtin(builtin, env)
             }
             ExpKind::Paren(exp) => self.eval_exp(exp, env),
-            ExpKind::Unary(op, exp) => self.eval_unop(op, exp, env),
-            ExpKind::ArrayInit { size } => {
-                let size = self.eval_exp(size, env)?;
-                Ok(Value::from(vec![0f64; Value::into_f64(size)? as usize]))
-            },
-            ExpKind::ArrayAccess { name, index } => {
-                let arr = Value::into_vec(env.get_var(name)?)?;
-                let i = Value::into_f64(self.eval_exp(index, env)?)?;
-                Ok(Value::from(arr[i as usize]))
+            ExpKind::Unary(op, exp) => ops::unary(&op.unop, self.eval_exp(exp, env)?, self.strict_conditions),
+            ExpKind::Array(exps) => {
+                let mut elements = Vec::new();
+                for exp in &exps.exps {
+                    elements.push(self.eval_exp(exp, env)?);
+                }
+                Ok(Value::from(elements))
+            }
+            ExpKind::ArrayAccess { array, index } => {
+                let array = self.eval_exp(array, env)?;
+                ops::index(array, self.eval_exp(index, env)?)
             }
-        };
-
-        if res.is_err() {
-            eprintln!("Error: ln:{}", exp.src_ln);
         }
-
-        res
     }
 
     fn eval_builtin(
@@ -137,47 +364,14 @@ impl Interpreter {
         builtin: &BuiltIn,
         env: &mut Environment,
     ) -> Result<Value, InterpreterError> {
-        match &builtin.builtin {
-            BuiltInKind::Sqrt(exps) => {
-                if exps.exps.len() != 1 {
-                    return Err(InterpreterError::ArgMismatch{expected: 1, got: exps.exps.len()});
-                }
-                let arg = Value::from(self.eval_exp(&exps.exps[0], env)?);
-                let float = Value::into_f64(arg)?;
-                Ok(Value::from(float.sqrt()))
-            },
-            BuiltInKind::Len(exps) => {
-                if exps.exps.len() != 1 {
-                    return Err(InterpreterError::ArgMismatch{expected: 1, got: exps.exps.len()});
-                }
-                let arg = Value::from(self.eval_exp(&exps.exps[0], env)?);
-                let arr = Value::into_vec(arg)?; 
-                Ok(Value::from(arr.len() as f64))
-            },
-            BuiltInKind::Round(exps) => {
-                if exps.exps.len() != 1 {
-                    return Err(InterpreterError::ArgMismatch{expected: 1, got: exps.exps.len()});
-                }
-                let arg = Value::from(self.eval_exp(&exps.exps[0], env)?);
-                let float = Value::into_f64(arg)?;
-                Ok(Value::from(float.round()))
-            },
-            BuiltInKind::Input(exps) => {
-                if exps.exps.len() != 0 {
-                    return Err(InterpreterError::ArgMismatch{expected: 0, got: exps.exps.len()});
-                }
-
-                print!("> ");
-                io::Write::flush(&mut io::stdout())?;
-
-                let mut buf = String::new();
-                self.input.borrow_mut().read_line(&mut buf)?;
+        let (func, exps) = BuiltinFn::of(&builtin.builtin);
+        ops::check_arity(func.arity(), exps.exps.len())?;
 
-                let float: f64 = buf.trim().parse()?;
-                Ok(Value::from(float))
-            },  
-            
+        let mut args = Vec::new();
+        for exp in &exps.exps {
+            args.push(self.eval_exp(exp, env)?);
         }
+        func.call(args, &mut self.input.borrow_mut(), &mut self.output.borrow_mut())
     }
 
     // Evaluates the given block in the given Environment
@@ -185,17 +379,32 @@ impl Interpreter {
         &self,
         block: &Block,
         env: &mut Environment,
-    ) -> Result<Option<Value>, InterpreterError> {
-        for statement in &block.statements {
-            let res = self.eval_statement(statement, env)?;
-            // if the statment is a return statment, stop evaluating and return as block result
-            if res.is_some() {
-                return Ok(res);
+    ) -> Result<Flow, InterpreterError> {
+        // variables declared in the block go away with it
+        self.eval_scoped(env, |interpreter, env| {
+            for statement in &block.statements {
+                let flow = interpreter.eval_statement(statement, env)?;
+                // if the statment returned, broke or continued, stop evaluating and leave the block with it
+                if !matches!(flow, Flow::Next) {
+                    return Ok(flow);
+                }
             }
-        }
 
-        // otherwise block has no value, return none
-        Ok(None)
+            // otherwise carry on after the block
+            Ok(Flow::Next)
+        })
+    }
+
+    // Evaluates eval in a new innermost frame of env, which is dropped afterwards even if eval fails
+    fn eval_scoped<T>(
+        &self,
+        env: &mut Environment,
+        eval: impl FnOnce(&Self, &mut Environment) -> Result<T, InterpreterError>,
+    ) -> Result<T, InterpreterError> {
+        env.push_frame();
+        let result = eval(self, env);
+        env.pop_frame();
+        result
     }
 
     // Evaluates the given statement in the given environment
@@ -203,42 +412,73 @@ impl Interpreter {
         &self,
         statement: &Statement,
         env: &mut Environment,
-    ) -> Result<Option<Value>, InterpreterError> {
+    ) -> Result<Flow, InterpreterError> {
+        // errors raised without a location happened in this statement, but outside any of its expressions
+        self.step()
+            .and_then(|_| self.eval_statement_kind(statement, env))
+            .map_err(|err| err.with_span(statement.span))
+    }
+
+    fn eval_statement_kind(
+        &self,
+        statement: &Statement,
+        env: &mut Environment,
+    ) -> Result<Flow, InterpreterError> {
         match &statement.statement {
-            StatementKind::Return(exp) => Ok(Some(self.eval_exp(&exp, env)?)),
-            StatementKind::Assign { name, exp } => {
-                // bind the variable
-                let value = self.eval_exp(&exp, env)?;
-                env.bind_var(name.clone(), value);
+            StatementKind::Return(exp) => Ok(Flow::Return(self.eval_exp(exp, env)?)),
+            StatementKind::Let { name, exp } => {
+                let value = self.eval_exp(exp, env)?;
+                env.declare_var(name.clone(), value);
                 // binds evalute to nothing
-                Ok(None)
+                Ok(Flow::Next)
             }
-            StatementKind::ArrayAssign {
-                name,
-                index_exp,
-                value,
-            } => {
-                let mut old = Value::into_vec(env.get_var(name)?)?;
-                let index = Value::into_f64(self.eval_exp(&index_exp, env)?)? as usize;
+            StatementKind::Assign { name, exp } => {
+                let value = self.eval_exp(exp, env)?;
+                if self.const_names.contains(name) && !env.is_declared(name) {
+                    return Err(InterpreterError::ConstAssign(name.clone(), Span::default()));
+                }
+                // unless declarations are strict, assigning an undeclared variable declares it
+                if env.is_declared(name) || self.strict_declarations {
+                    env.assign_var(name, value)?;
+                } else {
+                    env.declare_var(name.clone(), value);
+                }
+                Ok(Flow::Next)
+            }
+            StatementKind::ArrayAssign { name, path, value } => {
+                // arrays are shared, so updating the element updates every alias of the array.
+                // a const's array can't be changed through the const, but can through an alias of it
+                if self.const_names.contains(name) && !env.is_declared(name) {
+                    return Err(InterpreterError::ConstAssign(name.clone(), Span::default()));
+                }
+                let (last, inner) = path.split_last().expect("an lvalue has at least one index");
+                let mut array = self.get_var(name, env)?;
+                for index in inner {
+                    array = ops::index(array, self.eval_exp(index, env)?)?;
+                }
+                let index = self.eval_exp(last, env)?;
                 let new_val = self.eval_exp(value, env)?;
-                old[index] = Value::into_f64(new_val)?;
 
-                env.bind_var(name.clone(), Value::from(old));
-                Ok(None)
+                ops::set_index(array, index, new_val)?;
+                Ok(Flow::Next)
             }
             StatementKind::Exp(exp) => {
                 // statments composed of a single expression print but evaluate to nothing.
                 // e.g. 5+5;
                 // this will print "5" but the statement has no value
-                println!("{}", self.eval_exp(&exp, env)?);
-                Ok(None)
+                let value = self.eval_exp(exp, env)?;
+                self.output.borrow_mut().print(value)?;
+                Ok(Flow::Next)
             }
             StatementKind::Nest(nest) => self.eval_nest(nest, env),
+            StatementKind::Break => Ok(Flow::Break),
+            StatementKind::Continue => Ok(Flow::Continue),
         }
     }
 
     // Evaluates an expression of the form: lhs op rhs
     // Example: 2 + 7
+    // each operand is evaluated at most once, logical operators may skip rhs entirely.
     fn eval_infix(
         &self,
         lhs: &Exp,
@@ -246,50 +486,20 @@ impl Interpreter {
         rhs: &Exp,
         env: &mut Environment,
     ) -> Result<Value, InterpreterError> {
-        let lhs_val = Value::into_f64(self.eval_exp(lhs, env)?)?;
-        let rhs_val = Value::into_f64(self.eval_exp(rhs, env)?)?;
-
-        match &op.op {
-            OpKind::Logical(logical) => self.eval_logical(lhs, &logical, rhs, env),
-            OpKind::Comparison(comparison) => self.eval_comparison(lhs, comparison, rhs, env),
-            OpKind::Plus => Ok(Value::from(lhs_val + rhs_val)),
-            OpKind::Mul => Ok(Value::from(lhs_val * rhs_val)),
-            OpKind::Minus => Ok(Value::from(lhs_val - rhs_val)),
-            OpKind::Div => {
-                if rhs_val.abs() < EPSILON {
-                    return Err(InterpreterError::DivideByZero);
-                }
-                Ok(Value::from(lhs_val / rhs_val))
-            }
-            OpKind::Mod => {
-                if rhs_val.abs() < EPSILON {
-                    return Err(InterpreterError::DivideByZero);
-                }
-                Ok(Value::from(lhs_val % rhs_val))
-            }
-        }
-    }
-
-    // Evaluates an expression of the form: unop exp
-    // Example: -5
-    fn eval_unop(
-        &self,
-        unop: &Unop,
-        exp: &Exp,
-        env: &mut Environment,
-    ) -> Result<Value, InterpreterError> {
-        let value = Value::into_f64(self.eval_exp(exp, env)?)?;
+        // logical operators decide for themselves if rhs needs evaluating
+        let op = match &op.op {
+            OpKind::Logical(logical) => return self.eval_logical(lhs, logical, rhs, env),
+            op => BinaryOp::of(op).expect("non-logical operators are binary ops"),
+        };
 
-        match unop.unop {
-            UnopKind::Not => Ok(Value::from(helpers::bool_to_float(
-                !helpers::truthy(value),
-            ))),
-            UnopKind::Neg => Ok(Value::from(-value)),
-        }
+        let lhs_val = self.eval_exp(lhs, env)?;
+        let rhs_val = self.eval_exp(rhs, env)?;
+        ops::binary(op, lhs_val, rhs_val)
     }
 
     // Evaluates an expression of the form: lhs logical rhs
     // Example: a && b
+    // rhs is only evaluated if lhs does not already decide the result
     fn eval_logical(
         &self,
         lhs: &Exp,
@@ -297,129 +507,113 @@ impl Interpreter {
         rhs: &Exp,
         env: &mut Environment,
     ) -> Result<Value, InterpreterError> {
-        let lhs_val = helpers::truthy(Value::into_f64(self.eval_exp(lhs, env)?)?);
-        let rhs_val = helpers::truthy(Value::into_f64(self.eval_exp(rhs, env)?)?);
+        let lhs_val = self.truthy(lhs, env)?;
 
-        match logical.logical {
-            LogicalKind::Or => Ok(Value::from(helpers::bool_to_float(lhs_val || rhs_val))),
-            LogicalKind::And => Ok(Value::from(helpers::bool_to_float(lhs_val && rhs_val))),
-        }
-    }
+        let result = match logical.logical {
+            LogicalKind::Or => lhs_val || self.truthy(rhs, env)?,
+            LogicalKind::And => lhs_val && self.truthy(rhs, env)?,
+        };
 
-    // Evaluates an expression of the form: lhs comparison rhs
-    // Example: 5 >= 3
-    fn eval_comparison(
-        &self,
-        lhs: &Exp,
-        comparison: &Comparison,
-        rhs: &Exp,
-        env: &mut Environment,
-    ) -> Result<Value, InterpreterError> {
-        let lhs_val = Value::into_f64(self.eval_exp(lhs, env)?)?;
-        let rhs_val = Value::into_f64(self.eval_exp(rhs, env)?)?;
-
-        match comparison.comparison {
-            ComparisonKind::Equals => Ok(Value::from(helpers::bool_to_float(
-                (lhs_val - rhs_val).abs() < EPSILON,
-            ))),
-            // TODO: epsilon checking for comparisons?
-            ComparisonKind::Less => Ok(Value::from(helpers::bool_to_float(lhs_val < rhs_val))),
-            ComparisonKind::More => Ok(Value::from(helpers::bool_to_float(lhs_val > rhs_val))),
-            ComparisonKind::LessEqual => {
-                Ok(Value::from(helpers::bool_to_float(lhs_val <= rhs_val)))
-            }
-            ComparisonKind::MoreEqual => {
-                Ok(Value::from(helpers::bool_to_float(lhs_val >= rhs_val)))
-            }
-            ComparisonKind::NotEqual => Ok(Value::from(helpers::bool_to_float(
-                (lhs_val - rhs_val).abs() > EPSILON,
-            ))),
-        }
+        Ok(Value::from(result))
     }
 
-    
+    // Evaluates the truthiness of a condition
+    fn truthy(&self, cond: &Exp, env: &mut Environment) -> Result<bool, InterpreterError> {
+        ops::truthy(self.eval_exp(cond, env)?, self.strict_conditions)
+    }
 
     // Evaluates a nested expression
     // Example: if (5 > a) { return 1; }
-    fn eval_nest(
-        &self,
-        nest: &Nest,
-        env: &mut Environment,
-    ) -> Result<Option<Value>, InterpreterError> {
+    fn eval_nest(&self, nest: &Nest, env: &mut Environment) -> Result<Flow, InterpreterError> {
         match &nest.nest {
             NestKind::If { cond, then } => {
-                // evaluate truthiness of conditional expression
-                let cond_val = helpers::truthy(match self.eval_exp(&cond, env) {
-                    Ok(val) => Value::into_f64(val)?,
-                    Err(err) => return Err(err),
-                });
-
                 // if the condition is true, evaluate the block
-                if cond_val {
-                    match self.eval_block(&then, env) {
-                        // return the result of the block (will have value if block returned)
-                        Ok(opt) => return Ok(opt),
-                        Err(err) => return Err(err),
-                    }
+                if self.truthy(cond, env)? {
+                    return self.eval_block(then, env);
                 }
 
                 // if the condition is not true, do nothing
-                Ok(None)
+                Ok(Flow::Next)
             }
             NestKind::IfElse { cond, then, else_ } => {
-                // evaluate truthiness of conditional expression
-                let cond_val = helpers::truthy(match self.eval_exp(&cond, env) {
-                    Ok(val) => Value::into_f64(val)?,
-                    Err(err) => return Err(err),
-                });
-
-                // if the condition is true, evaluate the block
-                if cond_val {
-                    match self.eval_block(&then, env) {
-                        // return the result of the block (will have value if block returned)
-                        Ok(opt) => Ok(opt),
-                        Err(err) => Err(err),
-                    }
+                // evaluate the block the condition picks
+                if self.truthy(cond, env)? {
+                    self.eval_block(then, env)
                 } else {
-                    // else, evaluate the else block
-                    match self.eval_block(&else_, env) {
-                        // return the result of the block (will have value if block returned)
-                        Ok(opt) => Ok(opt),
-                        Err(err) => Err(err),
+                    self.eval_block(else_, env)
+                }
+            }
+            NestKind::IfChain { branches, else_ } => {
+                // evaluate the block of the first branch whose condition is true
+                for (cond, block) in branches {
+                    if self.truthy(cond, env)? {
+                        return self.eval_block(block, env);
                     }
                 }
+                match else_ {
+                    Some(else_) => self.eval_block(else_, env),
+                    None => Ok(Flow::Next),
+                }
             }
             NestKind::While { cond, block } => {
-                // evaluate truthiness of conditional expression
-                let mut cond_val = helpers::truthy(match self.eval_exp(&cond, env) {
-                    Ok(val) => Value::into_f64(val)?,
-                    Err(err) => return Err(err),
-                });
-
                 // while the condition is truthy
-                while cond_val {
-                    // execute the block
-                    match self.eval_block(block, env) {
-                        Ok(opt) => {
-                            // return the result of the block (will have value if block returned).
-                            // here we only return if block is some, as we don't want to exit block
-                            // if there was no return
-                            if opt.is_some() {
-                                return Ok(opt);
-                            }
-                        }
-                        Err(err) => return Err(err),
+                while self.truthy(cond, env)? {
+                    if let Some(flow) = self.eval_loop_block(block, env)? {
+                        return Ok(flow);
                     }
-
-                    // update the condition
-                    cond_val = helpers::truthy(match self.eval_exp(&cond, env) {
-                        Ok(val) => Value::into_f64(val)?,
-                        Err(err) => return Err(err),
-                    });
                 }
-                // exit loop
-                Ok(None)
+                Ok(Flow::Next)
+            }
+            NestKind::For {
+                init,
+                cond,
+                step,
+                block,
+            } => {
+                // variables declared in the header belong to the loop
+                self.eval_scoped(env, |interpreter, env| {
+                    interpreter.eval_statement(init, env)?;
+                    while interpreter.truthy(cond, env)? {
+                        if let Some(flow) = interpreter.eval_loop_block(block, env)? {
+                            return Ok(flow);
+                        }
+                        // continue still runs the step
+                        interpreter.eval_statement(step, env)?;
+                    }
+                    Ok(Flow::Next)
+                })
             }
+            NestKind::ForIn {
+                name,
+                iterable,
+                block,
+            } => {
+                // the iterable is evaluated once, its elements are looked up as the loop reaches them
+                let iterable = self.eval_exp(iterable, env)?;
+                // the loop variable belongs to the loop, shadowing any outer variable with the same name
+                self.eval_scoped(env, |interpreter, env| {
+                    let mut position = 0;
+                    while let Some(element) = ops::iterate(&iterable, position)? {
+                        env.declare_var(name.clone(), element);
+                        position += 1;
+                        if let Some(flow) = interpreter.eval_loop_block(block, env)? {
+                            return Ok(flow);
+                        }
+                    }
+                    Ok(Flow::Next)
+                })
+            }
+        }
+    }
+
+    // Evaluates one iteration of a loop's block.
+    // returns the Flow to leave the loop with, or None if the loop goes on
+    fn eval_loop_block(&self, block: &Block, env: &mut Environment) -> Result<Option<Flow>, InterpreterError> {
+        match self.eval_block(block, env)? {
+            // the loop goes around again, unless it has run out of time
+            Flow::Next | Flow::Continue => self.check_deadline().map(|_| None),
+            Flow::Break => Ok(Some(Flow::Next)),
+            flow => Ok(Some(flow)),
         }
     }
 }
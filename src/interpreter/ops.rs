@@ -0,0 +1,333 @@
+use super::*;
+
+// Operations on evaluated Values, shared by the Interpreter and the vm so both backends agree.
+// errors are raised with placeholder spans, to be filled in by whoever evaluated the operands.
+
+// A binary operator that doesn't short-circuit, i.e. anything but "&&" and "||"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Equals,
+    NotEqual,
+    Less,
+    More,
+    LessEqual,
+    MoreEqual,
+}
+
+impl BinaryOp {
+    // the BinaryOp for op, or None for logical operators
+    pub fn of(op: &OpKind) -> Option<BinaryOp> {
+        Some(match op {
+            OpKind::Logical(_) => return None,
+            OpKind::Comparison(comparison) => match comparison.comparison {
+                ComparisonKind::Equals => BinaryOp::Equals,
+                ComparisonKind::NotEqual => BinaryOp::NotEqual,
+                ComparisonKind::Less => BinaryOp::Less,
+                ComparisonKind::More => BinaryOp::More,
+                ComparisonKind::LessEqual => BinaryOp::LessEqual,
+                ComparisonKind::MoreEqual => BinaryOp::MoreEqual,
+            },
+            OpKind::Plus => BinaryOp::Add,
+            OpKind::Minus => BinaryOp::Sub,
+            OpKind::Mul => BinaryOp::Mul,
+            OpKind::Div => BinaryOp::Div,
+            OpKind::Mod => BinaryOp::Mod,
+        })
+    }
+}
+
+// Applies op to two evaluated operands
+// Example: 2 + 7
+// values of the same type can be compared for equality, strings can be concatenated,
+// everything else needs numbers
+pub fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, InterpreterError> {
+    match (op, lhs) {
+        (BinaryOp::Add, Value::Str(lhs)) => Ok(Value::from(format!("{}{}", lhs, Value::into_str(rhs)?))),
+        (BinaryOp::Equals, lhs) => Ok(Value::from(equal(lhs, rhs)?)),
+        (BinaryOp::NotEqual, lhs) => Ok(Value::from(!equal(lhs, rhs)?)),
+        (op, lhs) => numeric(op, Value::into_f64(lhs)?, Value::into_f64(rhs)?),
+    }
+}
+
+// Compares two values of the same type, numbers within EPSILON of each other are equal
+fn equal(lhs: Value, rhs: Value) -> Result<bool, InterpreterError> {
+    Ok(match lhs {
+        Value::Str(lhs) => lhs == Value::into_str(rhs)?,
+        Value::Bool(lhs) => lhs == Value::into_bool(rhs)?,
+        lhs => (Value::into_f64(lhs)? - Value::into_f64(rhs)?).abs() < EPSILON,
+    })
+}
+
+// Applies op to two numbers
+fn numeric(op: BinaryOp, lhs: f64, rhs: f64) -> Result<Value, InterpreterError> {
+    Ok(match op {
+        BinaryOp::Add => Value::from(lhs + rhs),
+        BinaryOp::Sub => Value::from(lhs - rhs),
+        BinaryOp::Mul => Value::from(lhs * rhs),
+        BinaryOp::Div => {
+            if rhs.abs() < EPSILON {
+                return Err(InterpreterError::DivideByZero(Span::default()));
+            }
+            Value::from(lhs / rhs)
+        }
+        BinaryOp::Mod => {
+            if rhs.abs() < EPSILON {
+                return Err(InterpreterError::DivideByZero(Span::default()));
+            }
+            Value::from(lhs % rhs)
+        }
+        BinaryOp::Equals | BinaryOp::NotEqual => unreachable!("equality is checked by equal"),
+        // TODO: epsilon checking for comparisons?
+        BinaryOp::Less => Value::from(lhs < rhs),
+        BinaryOp::More => Value::from(lhs > rhs),
+        BinaryOp::LessEqual => Value::from(lhs <= rhs),
+        BinaryOp::MoreEqual => Value::from(lhs >= rhs),
+    })
+}
+
+// Applies unop to an evaluated operand
+// Example: -5
+pub fn unary(unop: &UnopKind, value: Value, strict: bool) -> Result<Value, InterpreterError> {
+    match unop {
+        UnopKind::Not => Ok(Value::from(!truthy(value, strict)?)),
+        UnopKind::Neg => Ok(Value::from(-Value::into_f64(value)?)),
+    }
+}
+
+// Evaluates the truthiness of a condition or logical operand.
+// booleans are themselves, numbers are true unless within EPSILON of 0.
+// in strict mode only booleans are allowed
+pub fn truthy(value: Value, strict: bool) -> Result<bool, InterpreterError> {
+    match value {
+        Value::Bool(value) => Ok(value),
+        Value::Num(value) if !strict => Ok(helpers::truthy(value)),
+        other => Err(other.type_error(ValueDiscriminants::Bool)),
+    }
+}
+
+// Creates a zeroed array of the given size
+// Example: array(5)
+pub fn array_init(size: Value) -> Result<Value, InterpreterError> {
+    let size = Value::into_f64(size)?;
+    let invalid = InterpreterError::InvalidArraySize {
+        size,
+        span: Span::default(),
+    };
+
+    let len = match whole(size) {
+        Some(len) if len >= 0.0 && len.is_finite() => len as usize,
+        _ => return Err(invalid),
+    };
+    // sizes too large to allocate are reported rather than aborting
+    let mut array = Vec::new();
+    if array.try_reserve_exact(len).is_err() {
+        return Err(invalid);
+    }
+    array.resize(len, Value::from(0f64));
+    Ok(Value::from(array))
+}
+
+// Gets the element of an array, or the character of a string, at index
+// Example: arr[2]
+pub fn index(target: Value, index: Value) -> Result<Value, InterpreterError> {
+    if let Value::Str(string) = target {
+        let index = element(index, string.chars().count())?;
+        let char = string.chars().nth(index).expect("index is in bounds");
+        return Ok(Value::from(char.to_string()));
+    }
+
+    let array = Value::into_array(target)?;
+    let array = array.borrow();
+    Ok(array[element(index, array.len())?].clone())
+}
+
+// Replaces the element of array at index with value, in place
+// Example: arr[2] := 5;
+pub fn set_index(array: Value, index: Value, value: Value) -> Result<(), InterpreterError> {
+    let array = Value::into_array(array)?;
+    let index = element(index, array.borrow().len())?;
+    array.borrow_mut()[index] = value;
+    Ok(())
+}
+
+// Gets the element of an array, or the character of a string, at position in a for in loop,
+// None once position is past the end
+// Example: for x in arr { ... }
+pub fn iterate(target: &Value, position: usize) -> Result<Option<Value>, InterpreterError> {
+    match target {
+        Value::Str(string) => Ok(string.chars().nth(position).map(|char| Value::from(char.to_string()))),
+        Value::Array(array) => Ok(array.borrow().get(position).cloned()),
+        other => Err(other.type_error(ValueDiscriminants::Array)),
+    }
+}
+
+// Checks that index is a whole number in 0..len, returning it as a usize
+fn element(index: Value, len: usize) -> Result<usize, InterpreterError> {
+    let whole = whole_index(index)?;
+    if whole < 0.0 || whole >= len as f64 {
+        return Err(out_of_bounds(whole, len));
+    }
+    Ok(whole as usize)
+}
+
+fn out_of_bounds(index: f64, len: usize) -> InterpreterError {
+    InterpreterError::IndexOutOfBounds {
+        index,
+        len,
+        span: Span::default(),
+    }
+}
+
+// Converts index to the whole number it is, any whole number
+fn whole_index(index: Value) -> Result<f64, InterpreterError> {
+    let index = Value::into_f64(index)?;
+    whole(index).ok_or(InterpreterError::NonIntegerIndex {
+        index,
+        span: Span::default(),
+    })
+}
+
+// Returns the whole number value is, if it is within EPSILON of one.
+// infinities count as whole, NaN doesn't
+fn whole(value: f64) -> Option<f64> {
+    let rounded = value.round();
+    if (value - rounded).abs() < EPSILON || value.is_infinite() {
+        Some(rounded)
+    } else {
+        None
+    }
+}
+
+// A built-in function, without its arguments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuiltinFn {
+    Sqrt,
+    Len,
+    Round,
+    Input,
+    ToStr,
+    ToNum,
+    Substr,
+    Split,
+    Array,
+}
+
+impl BuiltinFn {
+    // splits a built-in call into the function and its argument expressions
+    pub fn of(builtin: &BuiltInKind) -> (BuiltinFn, &Exps) {
+        match builtin {
+            BuiltInKind::Sqrt(exps) => (BuiltinFn::Sqrt, exps),
+            BuiltInKind::Len(exps) => (BuiltinFn::Len, exps),
+            BuiltInKind::Round(exps) => (BuiltinFn::Round, exps),
+            BuiltInKind::Input(exps) => (BuiltinFn::Input, exps),
+            BuiltInKind::ToStr(exps) => (BuiltinFn::ToStr, exps),
+            BuiltInKind::ToNum(exps) => (BuiltinFn::ToNum, exps),
+            BuiltInKind::Substr(exps) => (BuiltinFn::Substr, exps),
+            BuiltInKind::Split(exps) => (BuiltinFn::Split, exps),
+            BuiltInKind::Array(exps) => (BuiltinFn::Array, exps),
+        }
+    }
+
+    // the number of arguments this function takes
+    pub fn arity(self) -> usize {
+        match self {
+            BuiltinFn::Input => 0,
+            BuiltinFn::Sqrt
+            | BuiltinFn::Len
+            | BuiltinFn::Round
+            | BuiltinFn::ToStr
+            | BuiltinFn::ToNum
+            | BuiltinFn::Array => 1,
+            BuiltinFn::Split => 2,
+            BuiltinFn::Substr => 3,
+        }
+    }
+
+    // the keyword this function is called with
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinFn::Sqrt => "sqrt",
+            BuiltinFn::Len => "len",
+            BuiltinFn::Round => "round",
+            BuiltinFn::Input => "input",
+            BuiltinFn::ToStr => "str",
+            BuiltinFn::ToNum => "num",
+            BuiltinFn::Substr => "substr",
+            BuiltinFn::Split => "split",
+            BuiltinFn::Array => "array",
+        }
+    }
+
+    // Calls this function with evaluated args, of which there must be arity
+    pub fn call(self, args: Vec<Value>, input: &mut Input, output: &mut Output) -> Result<Value, InterpreterError> {
+        let mut args = args.into_iter();
+        match self {
+            BuiltinFn::Sqrt => Ok(Value::from(Value::into_f64(args.next().unwrap())?.sqrt())),
+            BuiltinFn::Len => match args.next().unwrap() {
+                Value::Str(string) => Ok(Value::from(string.chars().count() as f64)),
+                other => Ok(Value::from(Value::into_array(other)?.borrow().len() as f64)),
+            },
+            BuiltinFn::Round => Ok(Value::from(Value::into_f64(args.next().unwrap())?.round())),
+            BuiltinFn::Input => {
+                output.prompt()?;
+
+                let mut buf = String::new();
+                input.read_line(&mut buf)?;
+
+                let float: f64 = buf.trim().parse()?;
+                Ok(Value::from(float))
+            }
+            BuiltinFn::ToStr => Ok(Value::from(args.next().unwrap().to_string())),
+            BuiltinFn::ToNum => {
+                let float: f64 = Value::into_str(args.next().unwrap())?.trim().parse()?;
+                Ok(Value::from(float))
+            }
+            BuiltinFn::Substr => {
+                let string = Value::into_str(args.next().unwrap())?;
+                let start = whole_index(args.next().unwrap())?;
+                let count = whole_index(args.next().unwrap())?;
+
+                // the substring must start and end within the string
+                let len = string.chars().count();
+                if start < 0.0 || start > len as f64 {
+                    return Err(out_of_bounds(start, len));
+                }
+                if count < 0.0 || start + count > len as f64 {
+                    return Err(out_of_bounds(start + count, len));
+                }
+                let substr: String = string.chars().skip(start as usize).take(count as usize).collect();
+                Ok(Value::from(substr))
+            }
+            BuiltinFn::Split => {
+                let string = Value::into_str(args.next().unwrap())?;
+                let separator = Value::into_str(args.next().unwrap())?;
+
+                // an empty separator splits into characters
+                let parts: Vec<Value> = if separator.is_empty() {
+                    string.chars().map(|c| Value::from(c.to_string())).collect()
+                } else {
+                    string.split(&*separator).map(Value::from).collect()
+                };
+                Ok(Value::from(parts))
+            }
+            BuiltinFn::Array => array_init(args.next().unwrap()),
+        }
+    }
+}
+
+// Ensures a call supplies as many arguments as the function takes
+pub fn check_arity(expected: usize, got: usize) -> Result<(), InterpreterError> {
+    if got != expected {
+        return Err(InterpreterError::ArgMismatch {
+            got,
+            expected,
+            span: Span::default(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{cell::RefCell, io::{self, Stdout, Write}, rc::Rc};
+
+use super::errors::InterpreterError;
+
+// prompt printed by input() unless it is changed
+const DEFAULT_PROMPT: &str = "> ";
+
+pub enum OutputKind {
+    Stdout(Stdout),
+    Buffer(Buffer),
+    Writer(Box<dyn Write>),
+    // a writer also written to elsewhere, e.g. by the REPL between entries
+    Shared(Rc<RefCell<dyn Write>>),
+}
+
+// Output is where a program's printed values and input() prompts go
+pub struct Output {
+    writer: OutputKind,
+    // printed before input() reads a line, None for no prompt
+    prompt: Option<String>,
+}
+
+// Buffer keeps everything written to it in memory.
+// clones share the same contents, so one can be read after another is given to an Output
+#[derive(Debug, Clone, Default)]
+pub struct Buffer {
+    contents: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer::default()
+    }
+
+    // Returns everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.contents.borrow()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.contents.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<Stdout> for Output {
+    fn from(s: Stdout) -> Self {
+        Output::of(OutputKind::Stdout(s))
+    }
+}
+
+impl From<Buffer> for Output {
+    fn from(b: Buffer) -> Self {
+        Output::of(OutputKind::Buffer(b))
+    }
+}
+
+impl From<Box<dyn Write>> for Output {
+    fn from(w: Box<dyn Write>) -> Self {
+        Output::of(OutputKind::Writer(w))
+    }
+}
+
+impl From<Rc<RefCell<dyn Write>>> for Output {
+    fn from(w: Rc<RefCell<dyn Write>>) -> Self {
+        Output::of(OutputKind::Shared(w))
+    }
+}
+
+impl Output {
+
+    fn of(writer: OutputKind) -> Output {
+        Output {
+            writer,
+            prompt: Some(DEFAULT_PROMPT.to_string()),
+        }
+    }
+
+    // Sets the prompt input() prints before reading a line, None turns it off
+    pub fn with_prompt(mut self, prompt: Option<&str>) -> Output {
+        self.prompt = prompt.map(str::to_string);
+        self
+    }
+
+    // Writes value on a line of its own
+    pub fn print(&mut self, value: impl std::fmt::Display) -> Result<(), InterpreterError> {
+        self.with_writer(|writer| writeln!(writer, "{}", value)).map_err(InterpreterError::output)
+    }
+
+    // Writes the input prompt, if there is one, so that it shows before input is read
+    pub fn prompt(&mut self) -> Result<(), InterpreterError> {
+        if let Some(prompt) = &self.prompt {
+            let prompt = prompt.clone();
+            self.with_writer(|writer| {
+                write!(writer, "{}", prompt)?;
+                writer.flush()
+            })
+            .map_err(InterpreterError::output)?;
+        }
+        Ok(())
+    }
+
+    fn with_writer<T>(&mut self, write: impl FnOnce(&mut dyn Write) -> io::Result<T>) -> io::Result<T> {
+        match &mut self.writer {
+            OutputKind::Stdout(s) => write(s),
+            OutputKind::Buffer(b) => write(b),
+            OutputKind::Writer(w) => write(w.as_mut()),
+            OutputKind::Shared(w) => write(&mut *w.borrow_mut()),
+        }
+    }
+}
@@ -1,14 +1,21 @@
 /*
 LANGUAGE GRAMMAR
 SOURCE: http://canonical.org/~kragen/memory-models/
-program ::= def*
+program ::= (def | const)*
 def ::= "def" name "(" args ")" block
+const ::= "const" name ":=" exp ";"
 args ::= "" | name "," args
 block ::= "{" statement* "}" 
-statement ::= "return" exp ";" | name ":=" exp ";" | name "[" exp "]" ":=" exp ";"|  exp ";" | nest
-nest ::= "if" (exp) block | "if" (exp) block "else" block | "while" (exp) block
-exp ::= name | num | "[" exp "]" | exp op exp | name "[" exp "]" | name "(" exps ")" | builtin | "(" exp ")" | unop exp
+statement ::= "return" exp ";" | simple ";" | "break" ";" | "continue" ";" | nest
+simple ::= "let" name ":=" exp | name ":=" exp | lvalue ":=" exp | exp
+lvalue ::= name "[" exp "]" | lvalue "[" exp "]"
+nest ::= "if" (exp) block | "if" (exp) block "else" block | "if" (exp) block ("else" "if" (exp) block)+ ("else" block)?
+    | "while" (exp) block
+    | "for" "(" simple ";" exp ";" simple ")" block | "for" name "in" exp block
+exp ::= name | num | string | "true" | "false" | "[" exps "]" | exp op exp | exp "[" exp "]" | name "(" exps ")" | builtin | "(" exp ")" | unop exp
 builtin ::= "sqrt" "(" exps ")" | "len" "(" exps ")" | "round" "(" exps ")" | "input" "(" exps ")"
+    | "str" "(" exps ")" | "num" "(" exps ")" | "substr" "(" exps ")" | "split" "(" exps ")"
+    | "array" "(" exps ")"
 exps ::= "" | exp "," exps
 unop ::= "!" | "-"
 op ::= logical | comparison | "+" | "*" | "-" | "/" | "%"
@@ -16,11 +23,31 @@ logical ::= "||" | "&&"
 comparison ::= "==" | "<" | ">" | "<=" | ">=" | "!="
 */
 
+use crate::span::Span;
 
-// program ::= def*
+// program ::= (def | const)*
 #[derive(Debug, Clone)]
 pub struct Program {
-    pub defs: Vec<Def>
+    pub defs: Vec<Def>,
+    pub consts: Vec<Const>,
+}
+
+// item ::= def | const | statement
+// a top-level entry of an interactive session, where statements may appear outside any def
+#[derive(Debug, Clone)]
+pub enum Item {
+    Def(Def),
+    Const(Const),
+    Statement(Statement),
+}
+
+// const ::= "const" name ":=" exp ";"
+// a global evaluated once, in order, before main. every def can see it, but nothing can assign to it
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: String,
+    pub exp: Exp,
+    pub span: Span,
 }
 
 // def ::= "def" name "(" args ")" block
@@ -29,56 +56,82 @@ pub struct Def {
     pub name: String,
     pub args: Args,
     pub block: Block,
+    pub span: Span,
 }
 
 // args ::= "" | name "," args
 #[derive(Debug, Clone)]
 pub struct Args {
-    pub names: Vec<String>
+    pub names: Vec<String>,
+    // where each name is, in the same order
+    pub spans: Vec<Span>,
 }
 
 // block ::= "{" statement* "}"
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub statements: Vec<Statement>
+    pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
-// statement ::= "return" exp ";" | name ":=" exp ";" | name "[" num "]" := exp ";"|  exp ";" | nest
+// statement ::= "return" exp ";" | simple ";" | "break" ";" | "continue" ";" | nest
+// simple ::= "let" name ":=" exp | name ":=" exp | lvalue ":=" exp | exp
+// lvalue ::= name "[" exp "]" | lvalue "[" exp "]"
 #[derive(Debug, Clone)]
 pub enum StatementKind {
     Return(Exp),
+    // declares name in the innermost block
+    Let {name: String, exp: Exp},
+    // assigns to the innermost variable called name
     Assign {name: String, exp: Exp},
-    ArrayAssign {name: String, index_exp: Exp, value: Exp},
+    // path holds the indices leading from the array in name to the element assigned, outermost first
+    ArrayAssign {name: String, path: Vec<Exp>, value: Exp},
     Exp(Exp),
     Nest(Nest),
+    // only parsed inside loops
+    Break,
+    Continue,
 }
 
 #[derive(Debug, Clone)]
 pub struct Statement {
-    pub statement: StatementKind
+    pub statement: StatementKind,
+    pub span: Span,
 }
 
-// nest ::= "if" (exp) block | "if" (exp) block "else" block | "while" (exp) block
+// nest ::= "if" (exp) block | "if" (exp) block "else" block | "if" (exp) block ("else" "if" (exp) block)+ ("else" block)?
+//     | "while" (exp) block
+//     | "for" "(" simple ";" exp ";" simple ")" block | "for" name "in" exp block
 #[derive(Debug, Clone)]
 pub enum NestKind {
     If {cond: Exp, then: Block},
     IfElse {cond: Exp, then: Block, else_: Block},
+    // an if with else ifs, kept flat: the block of the first branch whose cond holds runs,
+    // or else_ if none does
+    IfChain {branches: Vec<(Exp, Block)>, else_: Option<Block>},
     While {cond: Exp, block: Block},
+    // init runs once, then block and step run for as long as cond holds
+    For {init: Box<Statement>, cond: Exp, step: Box<Statement>, block: Block},
+    // name is assigned each element of an array, or each character of a string, in turn
+    ForIn {name: String, iterable: Exp, block: Block},
 }
 
 #[derive(Debug, Clone)]
 pub struct Nest {
-    pub nest: NestKind
+    pub nest: NestKind,
+    pub span: Span,
 }
 
-// exp ::= name | num | "[" exp "]" | exp op exp | name "[" exp "]" | name "(" exps ")" | "(" exp ")" | unop exp
+// exp ::= name | num | string | "true" | "false" | "[" exps "]" | exp op exp | exp "[" exp "]" | name "(" exps ")" | "(" exp ")" | unop exp
 #[derive(Debug, Clone)]
 pub enum ExpKind {
     Name(String),
     Num(f64),
-    ArrayInit{size: Exp},
+    Str(String),
+    Bool(bool),
+    Array(Exps),
     Infix(Exp, Op, Exp),
-    ArrayAccess{name: String, index: Exp},
+    ArrayAccess{array: Exp, index: Exp},
     Call(String, Exps),
     BuiltIn(BuiltIn),
     Paren(Exp),
@@ -88,7 +141,7 @@ pub enum ExpKind {
 #[derive(Debug, Clone)]
 pub struct Exp {
     pub exp: Box<ExpKind>,
-    pub src_ln: u32,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -97,6 +150,11 @@ pub enum BuiltInKind {
     Len(Exps),
     Round(Exps),
     Input(Exps),
+    ToStr(Exps),
+    ToNum(Exps),
+    Substr(Exps),
+    Split(Exps),
+    Array(Exps),
 }
 
 #[derive(Debug, Clone)]
@@ -111,7 +169,7 @@ pub struct Exps {
 }
 
 // unop ::= "!" | "-" 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnopKind {
     Not,
     Neg,
@@ -124,6 +182,7 @@ pub struct Unop {
 
 
 // op ::= logical | comparison | "+" | "*" | "-" | "/" | "%"
+// precedence, loosest first: "||", "&&", comparison, "+" "-", "*" "/" "%". all are left-associative.
 #[derive(Debug, Clone)]
 pub enum OpKind {
     Logical(Logical),
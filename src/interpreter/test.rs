@@ -3,6 +3,7 @@
 #[cfg(test)]
 mod tests {
     use super::super::*;
+    use crate::span::Span;
     #[test]
     fn test_assign_return() {
         /* AST for program:
@@ -18,7 +19,8 @@ mod tests {
                     name: String::from("main"),
                     // ()
                     args: Args {
-                        names: Vec::new()
+                        names: Vec::new(),
+                        spans: Vec::new(),
                     },
                     // {
                     block: Block {
@@ -30,9 +32,10 @@ mod tests {
                                     exp: Exp {
                                         // 1;
                                         exp: Box::new(ExpKind::Num(1f64)),
-                                        src_ln: 2
+                                        span: Span::default()
                                     }
-                                }
+                                },
+                                span: Span::default()
                             },
                             // return
                             Statement {
@@ -40,15 +43,19 @@ mod tests {
                                     Exp {
                                         // a;
                                         exp: Box::new(ExpKind::Name(String::from("a"))),
-                                        src_ln: 2
+                                        span: Span::default()
                                     }
-                                )
+                                ),
+                                span: Span::default()
                             }
-                        ]
+                        ],
+                        span: Span::default()
                     // }
                     },
+                    span: Span::default()
                 },
             ],
+            consts: Vec::new(),
         };
 
         let inter = Interpreter::new(prog);
@@ -76,19 +83,22 @@ mod tests {
                     }
                     */
                     name: String::from("other"),
-                    args: Args { names: vec![String::from("a")]},
+                    args: Args { names: vec![String::from("a")], spans: vec![Span::default()] },
                     block: Block {
                         statements: vec![
                             Statement {
                                 statement: StatementKind::Return(
                                     Exp {
                                         exp: Box::new(ExpKind::Name(String::from("a"))),
-                                        src_ln: 2
+                                        span: Span::default()
                                     }
-                                )
+                                ),
+                                span: Span::default()
                             }
-                        ]
-                    }
+                        ],
+                        span: Span::default()
+                    },
+                    span: Span::default()
                 },
                 /*
                 def main() {
@@ -97,7 +107,7 @@ mod tests {
                 */
                 Def {
                     name: String::from("main"),
-                    args: Args { names: Vec::new() },
+                    args: Args { names: Vec::new(), spans: Vec::new() },
                     block: Block {
                         statements: vec![
                             Statement {
@@ -105,15 +115,19 @@ mod tests {
                                 statement: StatementKind::Return(
                                     Exp {
                                         exp: Box::new(ExpKind::Call(String::from("other"),
-                                        Exps { exps: vec![ Exp { exp: Box::new(ExpKind::Num(3f64)), src_ln: 6}] })),
-                                        src_ln: 6
+                                        Exps { exps: vec![ Exp { exp: Box::new(ExpKind::Num(3f64)), span: Span::default()}] })),
+                                        span: Span::default()
                                     }
-                                )
+                                ),
+                                span: Span::default()
                             }
-                        ]
+                        ],
+                        span: Span::default()
                     },
+                    span: Span::default()
                 },
             ],
+            consts: Vec::new(),
         };
 
         let inter = Interpreter::new(prog);
@@ -3,6 +3,12 @@ extern crate strum_macros;
 extern crate strum;
 
 pub mod ast;
+pub mod check;
+pub mod compiler;
+pub mod diagnostics;
 pub mod interpreter;
+pub mod repl;
 pub mod scanner;
+pub mod span;
 pub mod tokens;
+pub mod vm;
@@ -1,40 +1,133 @@
 use std::{env, fs, process};
 
 use smp::ast;
+use smp::check;
+use smp::diagnostics::Diagnostic;
 use smp::{scanner::Scanner};
-use smp::interpreter::Interpreter;
-
-
-fn main(){
-    let mut scanner = Scanner::new(
-        get_source_file(env::args()).unwrap())
-            .unwrap();
-    let program = ast::generate_ast(&mut scanner).unwrap();
-    let interpreter = Interpreter::new(program);
-    let result = match interpreter.execute() {
-        Ok(res) => res,
-        Err(e) => {
-            eprintln!("Error: {:#?}", e);
+use smp::interpreter::symbols::Program;
+use smp::interpreter::{Interpreter, Value};
+use smp::repl::Repl;
+
+const USAGE: &str = "Usage: smp [filename.smp [args...] | check filename.smp | repl]";
+
+fn main() {
+    // with no file, or when asked for, start an interactive session
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args[1] == "repl" {
+        let result = Repl::new().run();
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if args[1] == "check" {
+        return check_file(args.get(2));
+    }
+
+    let (path, source, args) = match get_source_file(env::args()) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("{}", err);
             process::exit(1);
-        },
+        }
     };
-    if result.is_some() {
-        println!("Main: {}", result.unwrap());
-    } else {
-        println!("Main: None");
+    let program = parse(&path, &source);
 
+    let args = main_args(&program, &args);
+    let interpreter = Interpreter::builder(program).args(args).build();
+    match interpreter.execute_traced() {
+        Ok(Some(value)) => println!("Main: {}", value),
+        Ok(None) => println!("Main: None"),
+        Err(traceback) => {
+            eprint!("{}", traceback.render(&path, &source));
+            process::exit(1);
+        }
     }
 }
 
-fn get_source_file(args: env::Args) -> Result<String, String> {
+// reports every problem found in the source file at path without running it,
+// exiting with failure if any of them is an error
+fn check_file(path: Option<&String>) {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    let program = parse(path, &source);
+    let errors = check::check(&program);
+    let failed = errors.iter().any(|err| !err.is_warning());
+    for err in errors {
+        eprint!("{}", Diagnostic::from(err).render(path, &source));
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+// parses the source file at path, reporting every syntax error and exiting if there are any
+fn parse(path: &str, source: &str) -> Program {
+    let mut scanner = match Scanner::new(source.to_string()) {
+        Ok(scanner) => scanner,
+        Err(err) => report(Diagnostic::from(err), path, source),
+    };
+    let (program, errors) = ast::generate_ast_recovering(&mut scanner);
+    if !errors.is_empty() {
+        for err in errors {
+            eprint!("{}", Diagnostic::from(err).render(path, source));
+        }
+        process::exit(1);
+    }
+    program
+}
+
+// prints diagnostic for the source file at path and exits
+fn report(diagnostic: Diagnostic, path: &str, source: &str) -> ! {
+    eprint!("{}", diagnostic.render(path, source));
+    process::exit(1);
+}
+
+// returns the path and contents of the source file named in args, and the args after it
+fn get_source_file(args: env::Args) -> Result<(String, String, Vec<String>), String> {
     if args.len() < 2 {
-        return Err(String::from("Usage: smp filename.smp"));
+        return Err(String::from(USAGE));
     }
 
     let args: Vec<String> = args.collect();
 
     match fs::read_to_string(args[1].clone()) {
-        Ok(contents) => Ok(contents),
+        Ok(contents) => Ok((args[1].clone(), contents, args[2..].to_vec())),
         Err(err) => Err(err.to_string()),
     }
 }
+
+// converts command-line args into the args main is called with, each a number if it parses as one, else a string.
+// they are passed one per parameter, unless main takes a single parameter,
+// then they are always passed together as an array, however many there are
+fn main_args(program: &Program, args: &[String]) -> Vec<Value> {
+    let values: Vec<Value> = args
+        .iter()
+        .map(|arg| match arg.parse::<f64>() {
+            Ok(num) => Value::from(num),
+            Err(_) => Value::from(arg.as_str()),
+        })
+        .collect();
+
+    // a later def replaces an earlier one with the same name
+    let arity = program.defs.iter().rev().find(|def| def.name == "main").map(|def| def.args.names.len());
+    match arity {
+        Some(1) => vec![Value::from(values)],
+        _ => values,
+    }
+}
@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::rc::Rc;
+
+use crate::ast;
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::symbols::{Item, Program};
+use crate::interpreter::input::Input;
+use crate::interpreter::output::Output;
+use crate::interpreter::{Environment, Interpreter};
+use crate::scanner::Scanner;
+use crate::tokens::Token;
+
+#[cfg(test)]
+mod test;
+
+const PROMPT: &str = "smp> ";
+// shown while an entry is unfinished
+const CONTINUE_PROMPT: &str = "...> ";
+// path shown in diagnostics for entered source
+const REPL_PATH: &str = "<repl>";
+
+const HELP: &str = "\
+Enter defs, statements or expressions. Defs and variables persist between entries.
+Commands:
+  :defs         list defined functions
+  :vars         list consts and variables and their values
+  :load <file>  define every def and const in an smp source file
+  :reset        forget all defs and variables
+  :help         show this message
+  :quit         exit";
+
+// Repl is an interactive session, where defs and top-level variables persist between entries
+pub struct Repl {
+    interpreter: Interpreter,
+    env: Environment,
+    // where entries are read from, shared with input() so it carries on from the entry that called it
+    input: Rc<RefCell<dyn BufRead>>,
+    // where the session writes, shared with the interpreter so what entries print shows as it happens
+    output: Rc<RefCell<dyn Write>>,
+}
+
+// What the session does after a meta-command
+enum Flow {
+    Continue,
+    Quit,
+}
+
+impl Repl {
+    // Creates a session reading entries from stdin and writing to stdout
+    pub fn new() -> Repl {
+        Repl::with_io(BufReader::new(io::stdin()), io::stdout())
+    }
+
+    // Creates a session reading entries, and lines for input(), from input, and writing everything to output
+    pub fn with_io(input: impl BufRead + 'static, output: impl Write + 'static) -> Repl {
+        Repl::shared(Rc::new(RefCell::new(input)), Rc::new(RefCell::new(output)))
+    }
+
+    fn shared(input: Rc<RefCell<dyn BufRead>>, output: Rc<RefCell<dyn Write>>) -> Repl {
+        let program = Program {
+            defs: Vec::new(),
+            consts: Vec::new(),
+        };
+        Repl {
+            interpreter: Interpreter::new_with_io(program, Input::from(input.clone()), Output::from(output.clone())),
+            env: Environment::new(),
+            input,
+            output,
+        }
+    }
+
+    // Runs the session, reading entries until its input ends or :quit is entered.
+    // an entry continues over multiple lines until its braces and parens are balanced.
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut entry = String::new();
+        loop {
+            let prompt = if entry.is_empty() { PROMPT } else { CONTINUE_PROMPT };
+            self.write(prompt)?;
+
+            let mut line = String::new();
+            if self.input.borrow_mut().read_line(&mut line)? == 0 {
+                return self.write("\n");
+            }
+
+            // commands are only recognized at the start of an entry
+            if entry.is_empty() && line.trim_start().starts_with(':') {
+                match self.command(line.trim())? {
+                    Flow::Continue => continue,
+                    Flow::Quit => return Ok(()),
+                }
+            }
+
+            entry.push_str(&line);
+            if is_complete(&entry) {
+                self.eval(&std::mem::take(&mut entry))?;
+            }
+        }
+    }
+
+    // Evaluates an entry, binding its defs and executing its statements in order.
+    // values returned by statements and any errors are written to the session's output.
+    pub fn eval(&mut self, source: &str) -> io::Result<()> {
+        let source = terminate(source);
+        let mut scanner = match Scanner::new(source.clone()) {
+            Ok(scanner) => scanner,
+            Err(err) => return self.write(Diagnostic::from(err).render(REPL_PATH, &source)),
+        };
+
+        // nothing in an entry runs if any of it is malformed
+        let (items, errors) = ast::generate_items_recovering(&mut scanner);
+        if !errors.is_empty() {
+            for err in errors {
+                self.write(Diagnostic::from(err).render(REPL_PATH, &source))?;
+            }
+            return Ok(());
+        }
+
+        for item in items {
+            match item {
+                Item::Def(def) => self.interpreter.bind_def(def),
+                Item::Const(c) => {
+                    if let Err(err) = self.interpreter.bind_const(&c) {
+                        return self.write(err.render(REPL_PATH, &source));
+                    }
+                }
+                Item::Statement(statement) => match self.interpreter.eval_top_level(&statement, &mut self.env) {
+                    Ok(Some(value)) => self.write(format!("{}\n", value))?,
+                    Ok(None) => {}
+                    Err(err) => return self.write(err.render(REPL_PATH, &source)),
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    // Executes a meta-command, e.g. :defs
+    fn command(&mut self, line: &str) -> io::Result<Flow> {
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let command = parts.next().unwrap_or("");
+        let arg = parts.next().unwrap_or("").trim();
+
+        match command {
+            ":quit" | ":q" => return Ok(Flow::Quit),
+            ":help" | ":h" => self.write(format!("{}\n", HELP))?,
+            ":defs" => {
+                for def in self.interpreter.defs().iter() {
+                    self.write(format!("{}({})\n", def.name, def.args.names.join(", ")))?;
+                }
+            }
+            ":vars" => {
+                for (name, value) in self.interpreter.consts().iter() {
+                    self.write(format!("const {} = {}\n", name, value))?;
+                }
+                for (name, value) in self.env.iter() {
+                    self.write(format!("{} = {}\n", name, value))?;
+                }
+            }
+            ":load" if arg.is_empty() => self.write("usage: :load <file>\n")?,
+            ":load" => self.load(arg)?,
+            ":reset" => {
+                *self = Repl::shared(self.input.clone(), self.output.clone());
+                self.write("session reset\n")?;
+            }
+            other => self.write(format!("unknown command `{}`, see :help\n", other))?,
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    // Binds every def and const in the source file at path, without running main
+    fn load(&mut self, path: &str) -> io::Result<()> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => return self.write(format!("could not read {}: {}\n", path, err)),
+        };
+        let mut scanner = match Scanner::new(source.clone()) {
+            Ok(scanner) => scanner,
+            Err(err) => return self.write(Diagnostic::from(err).render(path, &source)),
+        };
+
+        let (program, errors) = ast::generate_ast_recovering(&mut scanner);
+        if !errors.is_empty() {
+            for err in errors {
+                self.write(Diagnostic::from(err).render(path, &source))?;
+            }
+            return Ok(());
+        }
+
+        let count = program.defs.len();
+        for def in program.defs {
+            self.interpreter.bind_def(def);
+        }
+        for c in &program.consts {
+            if let Err(err) = self.interpreter.bind_const(c) {
+                return self.write(err.render(path, &source));
+            }
+        }
+        if !program.consts.is_empty() {
+            return self.write(format!("loaded {} def(s) and {} const(s) from {}\n", count, program.consts.len(), path));
+        }
+        self.write(format!("loaded {} def(s) from {}\n", count, path))
+    }
+
+    // Writes text to the session's output, flushing it so that it shows before anything is read.
+    // the interpreter writes there too, so it is never borrowed while evaluating
+    fn write(&self, text: impl AsRef<str>) -> io::Result<()> {
+        let mut output = self.output.borrow_mut();
+        output.write_all(text.as_ref().as_bytes())?;
+        output.flush()
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns true if source has no unclosed braces, brackets or parens.
+// source that can't be scanned is complete, so that the error is reported right away.
+pub fn is_complete(source: &str) -> bool {
+    let scanner = match Scanner::new(source.to_string()) {
+        Ok(scanner) => scanner,
+        Err(_) => return true,
+    };
+
+    let mut depth = 0;
+    for token in scanner {
+        match token {
+            Ok(token) => match token.token {
+                Token::LCurly | Token::LParen | Token::LBracket => depth += 1,
+                Token::RCurly | Token::RParen | Token::RBracket => depth -= 1,
+                _ => {}
+            },
+            Err(_) => return true,
+        }
+    }
+
+    depth <= 0
+}
+
+// Terminates a trailing bare expression with a ;
+// so that `1 + 2` can be entered instead of `1 + 2;`
+fn terminate(source: &str) -> String {
+    let last = Scanner::new(source.to_string())
+        .ok()
+        .and_then(|scanner| scanner.filter_map(Result::ok).last());
+
+    match last {
+        Some(last) if !matches!(last.token, Token::SColon | Token::RCurly) => {
+            // on a new line, in case the source ends in a comment
+            format!("{}\n;", source)
+        }
+        _ => source.to_string(),
+    }
+}
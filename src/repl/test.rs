@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+
+    use super::super::*;
+    use crate::interpreter::output::Buffer;
+
+    // runs a session over the given input, returning everything it wrote
+    fn session(input: &str) -> String {
+        let output = Buffer::new();
+        Repl::with_io(Cursor::new(input.to_string()), output.clone())
+            .run()
+            .unwrap();
+        output.contents()
+    }
+
+    #[test]
+    fn test_persistent_defs_and_vars() {
+        let output = session(
+            "def sq(x) {\n\
+             return x * x;\n\
+             }\n\
+             a := sq(3);\n\
+             return a + 1;\n",
+        );
+        assert_eq!(output, "smp> ...> ...> smp> smp> 10\nsmp> \n");
+    }
+
+    #[test]
+    fn test_unfinished_input() {
+        assert!(is_complete("a := 1;"));
+        assert!(is_complete("def f() { return 1; }"));
+        assert!(!is_complete("def f() {"));
+        assert!(!is_complete("while (a < (1"));
+        // comments don't count
+        assert!(is_complete("a := 1; # {"));
+        // errors are reported immediately
+        assert!(is_complete("{ $"));
+    }
+
+    #[test]
+    fn test_commands() {
+        let output = session(
+            "def add(a, b) { return a + b; }\n\
+             x := add(1, 2);\n\
+             :defs\n\
+             :vars\n\
+             :reset\n\
+             :defs\n\
+             :vars\n\
+             :quit\n\
+             return 1;\n",
+        );
+        assert_eq!(
+            output,
+            "smp> smp> smp> add(a, b)\nsmp> x = 3\nsmp> session reset\nsmp> smp> smp> "
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join("smp_repl_test_load.smp");
+        std::fs::write(&path, "def one() { return 1; }\ndef two() { return 2; }\n").unwrap();
+
+        let output = session(&format!(":load {}\nreturn one() + two();\n", path.display()));
+        assert_eq!(
+            output,
+            format!("smp> loaded 2 def(s) from {}\nsmp> 3\nsmp> \n", path.display())
+        );
+    }
+
+    #[test]
+    fn test_printed_values() {
+        let output = session("def f(x) { x; return x + 1; }\n\"hi\";\nreturn f(1);\n");
+        assert_eq!(output, "smp> smp> hi\nsmp> 1\n2\nsmp> \n");
+    }
+
+    #[test]
+    fn test_input() {
+        // input() reads the line after the entry calling it, and the session carries on after that.
+        // its prompt shows before the line is read
+        let output = session("x := input() * 2;\n5\nreturn x;\n");
+        assert_eq!(output, "smp> > smp> 10\nsmp> \n");
+
+        // as does anything printed before it
+        let output = session("\"n?\"; x := input();\n5\n");
+        assert_eq!(output, "smp> n?\n> smp> \n");
+    }
+
+    #[test]
+    fn test_consts() {
+        let output = session("const N := 2;\nreturn N * 3;\nN := 1;\n:vars\n");
+        assert!(output.contains("smp> 6\n"));
+        assert!(output.contains("error: cannot assign to constant `N`"));
+        assert!(output.ends_with("smp> const N = 2\nsmp> \n"));
+    }
+
+    #[test]
+    fn test_errors_keep_session() {
+        let output = session("a := 1;\nb := a +;\nreturn b;\nreturn a\n");
+        assert!(output.contains("error: expected expression, found `;`"));
+        assert!(output.contains("error: unbound variable `b`"));
+        // the trailing ; can be left off
+        assert!(output.ends_with("smp> 1\nsmp> \n"));
+    }
+}
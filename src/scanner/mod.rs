@@ -1,14 +1,32 @@
-use crate::tokens::Token;
+use crate::span::Span;
+use crate::tokens::{SpannedToken, Token};
 
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod test;
 
 #[derive(Debug)]
 pub enum ScannerError {
-    UnexpectedToken(String),
-    UnexpectedEof,
-    InvalidNumeric(String),
+    UnexpectedToken(String, Span),
+    UnexpectedEof(Span),
+    InvalidNumeric(String, Span),
+    // a string literal without a closing quote on the line it starts
+    UnterminatedString(Span),
+    InvalidEscape(String, Span),
+}
+
+impl ScannerError {
+    // the source location this error was raised at
+    pub fn span(&self) -> Span {
+        match self {
+            ScannerError::UnexpectedToken(_, span)
+            | ScannerError::UnexpectedEof(span)
+            | ScannerError::InvalidNumeric(_, span)
+            | ScannerError::UnterminatedString(span)
+            | ScannerError::InvalidEscape(_, span) => *span,
+        }
+    }
 }
 
 const EOF_CHAR: char = '\0';
@@ -17,8 +35,10 @@ const EOF_CHAR: char = '\0';
 pub struct Scanner {
     input: String,
     cur: usize,
-    next: Token,
+    next: SpannedToken,
+    last: Span,
     pub src_line: u32,
+    line_start: usize,
 }
 
 impl Scanner {
@@ -28,8 +48,13 @@ impl Scanner {
         Scanner {
             input,
             cur: 0,
-            next: Token::Start,
+            next: SpannedToken {
+                token: Token::Start,
+                span: Span::default(),
+            },
+            last: Span::default(),
             src_line: 1,
+            line_start: 0,
         };
 
         scanner.next_token()?;
@@ -41,21 +66,49 @@ impl Scanner {
     }
 
     pub fn peek_next(&self) -> Token {
-        self.next.clone()
+        self.next.token.clone()
+    }
+
+    // returns the span of the next Token
+    pub fn peek_span(&self) -> Span {
+        self.next.span
+    }
+
+    // returns the span of the most recently consumed Token
+    pub fn last_span(&self) -> Span {
+        self.last
     }
     
     // gets and consumes the next Token
     pub fn next_token(&mut self) -> Result<Token, ScannerError> {
+        Ok(self.next_spanned()?.token)
+    }
+
+    // gets and consumes the next Token along with its span.
+    // if the token after it can't be scanned, the error is returned instead and the token stays next,
+    // the bad input is skipped so the following call can continue.
+    pub fn next_spanned(&mut self) -> Result<SpannedToken, ScannerError> {
         let result = self.next.clone();
-        self.next = self.get_next()?;
-        // println!("src: {}", self.src_line);
+        self.next = self.get_next_spanned()?;
+        self.last = result.span;
         Ok(result)
     }
 
-    // returns the next recognized token in the input
-    fn get_next(&mut self) -> Result<Token, ScannerError> {
+    // returns the next recognized token in the input along with its span
+    fn get_next_spanned(&mut self) -> Result<SpannedToken, ScannerError> {
         self.skip_whitespace();
 
+        let (start, line, col) = (self.cur, self.src_line, self.col());
+        let token = self.get_next()?;
+        Ok(SpannedToken {
+            token,
+            span: Span::new(start, self.cur, line, col),
+        })
+    }
+
+    // returns the next recognized token in the input,
+    // whitespace and comments must already be skipped.
+    fn get_next(&mut self) -> Result<Token, ScannerError> {
         if self.is_eof() {
             return Ok(Token::Eof);
         }
@@ -122,7 +175,7 @@ impl Scanner {
                     self.advance();
                     return Ok(Token::Assign);
                 }
-                Err(ScannerError::UnexpectedToken(self.get_char().to_string()))
+                Err(self.unexpected_char())
             }
             // multi-char operators
             '|'  => {
@@ -133,7 +186,7 @@ impl Scanner {
                    self.advance();
                    return Ok(Token::Or);
                 }
-                Err(ScannerError::UnexpectedToken(self.get_char().to_string()))
+                Err(self.unexpected_char())
             }
             '&' => {
                 // consume &
@@ -143,7 +196,7 @@ impl Scanner {
                    self.advance();
                    return Ok(Token::And);
                 }
-                Err(ScannerError::UnexpectedToken(self.get_char().to_string()))
+                Err(self.unexpected_char())
             }
             '!' => {
                 // consume !
@@ -163,7 +216,7 @@ impl Scanner {
                    self.advance();
                    return Ok(Token::Equals);
                 }
-                Err(ScannerError::UnexpectedToken(self.get_char().to_string()))
+                Err(self.unexpected_char())
             },
             '<' => {
                 // consume <
@@ -190,44 +243,70 @@ impl Scanner {
             '0'..='9' => {
                 self.parse_num()
             }
-            
+
+            // strings
+            '"' => self.parse_string(),
 
             // keywords, function names, variable names
             'a'..='z' | 'A'..='Z' => self.parse_word(),
             // eof
             EOF_CHAR => Ok(Token::Eof),
-            // comments
-            '#' => {
-                self.skip_line();
-                self.get_next()
-            }
             // unknown character
-            unknown => Err(ScannerError::UnexpectedToken(unknown.to_string())),
+            // consumed so that scanning can continue after reporting it
+            _ => {
+                let err = self.unexpected_char();
+                self.advance();
+                Err(err)
+            }
         }
     }
 
     // gets the character in input at cur.
     // returns the EOF_CHAR if we have overun input
     fn get_char(&self) -> char {
-        if self.input.len() > self.cur {
-            return self.input.as_bytes()[self.cur] as char;
+        self.input[self.cur..].chars().next().unwrap_or(EOF_CHAR)
+    }
+
+    // returns the column of cur, starting at 1, counting characters rather than bytes
+    fn col(&self) -> u32 {
+        self.input[self.line_start..self.cur].chars().count() as u32 + 1
+    }
+
+    // returns an error for the character at cur, which no token can start or continue with
+    fn unexpected_char(&self) -> ScannerError {
+        if self.is_eof() {
+            return ScannerError::UnexpectedEof(self.char_span());
         }
-        EOF_CHAR
+        ScannerError::UnexpectedToken(self.get_char().to_string(), self.char_span())
+    }
+
+    // returns the span of the single character at cur
+    fn char_span(&self) -> Span {
+        Span::new(self.cur, self.cur + self.get_char().len_utf8(), self.src_line, self.col())
     }
 
-    // advances scanner to next byte
+    // advances scanner to next character
     fn advance(&mut self) {
-        self.cur += 1;
+        self.cur += self.get_char().len_utf8();
     }
 
-    // skips over all whitespace in input
-    fn skip_whitespace(&mut self) {
+    // advances past a newline, moving to the start of the next line
+    fn advance_line(&mut self) {
+        self.advance();
+        self.src_line += 1;
+        self.line_start = self.cur;
+    }
 
-        while self.get_char().is_whitespace() {
-            if self.get_char() == '\n' {
-                self.src_line += 1;
+    // skips over all whitespace and comments in input
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.get_char() {
+                '\n' => self.advance_line(),
+                // comments
+                '#' => self.skip_line(),
+                c if c.is_whitespace() => self.advance(),
+                _ => return,
             }
-            self.advance();
         }
     }
 
@@ -238,8 +317,7 @@ impl Scanner {
         }
 
         if self.get_char() == '\n' {
-            self.advance();
-            self.src_line += 1;
+            self.advance_line();
         }
     }
 
@@ -249,7 +327,7 @@ impl Scanner {
     
         // keep consuming tokens until we reach a non-alphanumeric
         let mut word = String::new();
-        while self.get_char().is_alphanumeric() {
+        while self.get_char().is_ascii_alphanumeric() {
             word.push(self.get_char());
             // consume next
             self.advance();
@@ -266,6 +344,7 @@ impl Scanner {
     // parses a floating point number
     fn parse_num(&mut self) -> Result<Token, ScannerError> {
         assert!(self.get_char().is_ascii_digit());
+        let start = self.char_span();
         let mut num = String::new();
 
         // consume until we hit non digit or non .
@@ -277,9 +356,68 @@ impl Scanner {
         // let parse worry about extra .'s or other problems
         match num.parse() {
             Ok(val) => Ok(Token::Num(val)),
-            Err(_) => Err(ScannerError::InvalidNumeric(num)),
+            Err(_) => Err(ScannerError::InvalidNumeric(num, Span { end: self.cur, ..start })),
+        }
+
+    }
+
+    // parses a double-quoted string literal, replacing escape sequences with the characters they stand for.
+    // on an error the whole literal is still consumed, so that scanning can continue after it
+    fn parse_string(&mut self) -> Result<Token, ScannerError> {
+        assert!(self.get_char() == '"');
+        let open = self.char_span();
+        // consume "
+        self.advance();
+
+        let mut value = String::new();
+        let mut error = None;
+        // start of the characters not yet copied into value
+        let mut run = self.cur;
+        loop {
+            // strings can't span lines, the newline is left to be counted
+            if self.is_eof() || self.get_char() == '\n' {
+                return Err(ScannerError::UnterminatedString(Span { end: self.cur, ..open }));
+            }
+
+            match self.get_char() {
+                '"' => break,
+                '\\' => {
+                    value.push_str(&self.input[run..self.cur]);
+                    let escape = self.char_span();
+                    // consume \
+                    self.advance();
+
+                    let escaped = match self.input[self.cur..].chars().next() {
+                        Some(escaped) if escaped != '\n' => escaped,
+                        // unterminated, reported above
+                        _ => continue,
+                    };
+                    match escaped {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        '0' => value.push('\0'),
+                        '\\' | '"' => value.push(escaped),
+                        other => {
+                            let span = Span { end: self.cur + other.len_utf8(), ..escape };
+                            error.get_or_insert(ScannerError::InvalidEscape(format!("\\{}", other), span));
+                        }
+                    }
+                    self.cur += escaped.len_utf8();
+                    run = self.cur;
+                }
+                _ => self.advance(),
+            }
         }
 
+        value.push_str(&self.input[run..self.cur]);
+        // consume "
+        self.advance();
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(Token::Str(value)),
+        }
     }
 
     // tries to convert a str to a keyword,
@@ -288,27 +426,38 @@ impl Scanner {
         match word {
             "def" => Some(Token::Def),
             "return" => Some(Token::Return),
+            "let" => Some(Token::Let),
+            "const" => Some(Token::Const),
             "if" => Some(Token::If),
             "else" => Some(Token::Else),
             "while" => Some(Token::While),
+            "for" => Some(Token::For),
+            "in" => Some(Token::In),
+            "break" => Some(Token::Break),
+            "continue" => Some(Token::Continue),
+            "true" => Some(Token::True),
+            "false" => Some(Token::False),
             "sqrt" => Some(Token::Sqrt),
             "len" => Some(Token::Len),
             "round" => Some(Token::Round),
             "input" => Some(Token::Input),
+            "str" => Some(Token::ToStr),
+            "num" => Some(Token::ToNum),
+            "substr" => Some(Token::Substr),
+            "split" => Some(Token::Split),
+            "array" => Some(Token::Array),
             _ => None
         }
     }
 }
 
 impl Iterator for Scanner {
-    type Item = Result<Token, ScannerError>;
+    type Item = Result<SpannedToken, ScannerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let res = self.next_token();
-        if res.is_ok() {
-            if let Token::Eof = res.as_ref().unwrap() {
-                return None;
-            }
+        let res = self.next_spanned();
+        if let Ok(SpannedToken { token: Token::Eof, .. }) = res {
+            return None;
         }
         Some(res)
     }
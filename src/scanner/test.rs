@@ -3,6 +3,7 @@
 mod test {
 
     use super::super::*;
+    use crate::span::Span;
 
     #[test]
     fn one_plus_one() {
@@ -40,7 +41,30 @@ mod test {
 
         let mut actual = Vec::new();
         for token in s.into_iter() {
-            actual.push(token.unwrap());
+            actual.push(token.unwrap().token);
+        }
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn array_builtin() {
+        let s = Scanner::new(String::from(r#"
+        b := array(5);
+        "#)).unwrap();
+        let expected = vec![
+            Token::Name(String::from("b")),
+            Token::Assign,
+            Token::Array,
+            Token::LParen,
+            Token::Num(5f64),
+            Token::RParen,
+            Token::SColon,
+        ];
+
+        let mut actual = Vec::new();
+        for token in s.into_iter() {
+            actual.push(token.unwrap().token);
         }
 
         assert_eq!(expected, actual);
@@ -82,10 +106,115 @@ mod test {
 
         let mut actual = Vec::new();
         for token in s.into_iter() {
-            actual.push(token.unwrap());
+            actual.push(token.unwrap().token);
         }
 
         assert_eq!(expected, actual);
         
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn spans() {
+        let s = Scanner::new(String::from("a := 1.5;\n# comment\n  b >= a")).unwrap();
+        let expected = vec![
+            (Token::Name(String::from("a")), Span::new(0, 1, 1, 1)),
+            (Token::Assign, Span::new(2, 4, 1, 3)),
+            (Token::Num(1.5), Span::new(5, 8, 1, 6)),
+            (Token::SColon, Span::new(8, 9, 1, 9)),
+            (Token::Name(String::from("b")), Span::new(22, 23, 3, 3)),
+            (Token::MoreEqual, Span::new(24, 26, 3, 5)),
+            (Token::Name(String::from("a")), Span::new(27, 28, 3, 8)),
+        ];
+
+        let actual: Vec<(Token, Span)> = s
+            .into_iter()
+            .map(|token| {
+                let token = token.unwrap();
+                (token.token, token.span)
+            })
+            .collect();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn error_span() {
+        let mut s = Scanner::new(String::from("a\n  $")).unwrap();
+        match s.next_token() {
+            Err(ScannerError::UnexpectedToken(found, span)) => {
+                assert_eq!(found, "$");
+                assert_eq!(span, Span::new(4, 5, 2, 3));
+            }
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+
+        // columns count characters, not bytes
+        let mut s = Scanner::new(String::from("\"é€\" $")).unwrap();
+        match s.next_token() {
+            Err(ScannerError::UnexpectedToken(found, span)) => {
+                assert_eq!(found, "$");
+                assert_eq!(span, Span::new(8, 9, 1, 6));
+            }
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_ascii_char() {
+        // reported once, as the whole character, and skipped
+        let s = Scanner::new(String::from("a é€ b")).unwrap();
+        let (tokens, errors): (Vec<_>, Vec<_>) = s.into_iter().partition(|token| token.is_ok());
+        let tokens: Vec<Token> = tokens.into_iter().map(|token| token.unwrap().token).collect();
+        let errors: Vec<(String, Span)> = errors
+            .into_iter()
+            .map(|err| match err {
+                Err(ScannerError::UnexpectedToken(found, span)) => (found, span),
+                other => panic!("expected UnexpectedToken, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(tokens, [Token::Name(String::from("a")), Token::Name(String::from("b"))]);
+        assert_eq!(
+            errors,
+            [
+                (String::from("é"), Span::new(2, 4, 1, 3)),
+                (String::from("€"), Span::new(4, 7, 1, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let s = Scanner::new(String::from(r#"a := "hi \"there\"\n" + "é{";"#)).unwrap();
+        let expected = vec![
+            Token::Name(String::from("a")),
+            Token::Assign,
+            Token::Str(String::from("hi \"there\"\n")),
+            Token::Plus,
+            Token::Str(String::from("é{")),
+            Token::SColon,
+        ];
+        let actual: Vec<Token> = s.into_iter().map(|token| token.unwrap().token).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn string_errors() {
+        let mut s = Scanner::new(String::from("a \"b\\qc\" d\n\"open\nf")).unwrap();
+        match s.next_token() {
+            Err(ScannerError::InvalidEscape(escape, span)) => {
+                assert_eq!(escape, "\\q");
+                assert_eq!(span, Span::new(4, 6, 1, 5));
+            }
+            other => panic!("expected InvalidEscape, got {:?}", other),
+        }
+
+        // the bad literal is skipped
+        assert_eq!(s.next_token().unwrap(), Token::Name(String::from("a")));
+        match s.next_token() {
+            Err(ScannerError::UnterminatedString(span)) => assert_eq!(span, Span::new(11, 16, 2, 1)),
+            other => panic!("expected UnterminatedString, got {:?}", other),
+        }
+        assert_eq!(s.next_token().unwrap(), Token::Name(String::from("d")));
+        assert_eq!(s.next_token().unwrap(), Token::Name(String::from("f")));
+    }
+}
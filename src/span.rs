@@ -0,0 +1,33 @@
+// Span marks the region of source code a token or AST node was parsed from.
+// the default span (line 0) is a placeholder for locations that are not known yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize, // byte offset of the first character
+    pub end: usize,   // byte offset one past the last character
+    pub line: u32,    // line of the first character, starting at 1
+    pub col: u32,     // column of the first character, starting at 1
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: u32, col: u32) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            col,
+        }
+    }
+
+    // returns a span covering from the start of self to the end of other
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self
+        }
+    }
+
+    // returns true if this is the placeholder span
+    pub fn is_dummy(&self) -> bool {
+        *self == Span::default()
+    }
+}
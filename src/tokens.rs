@@ -1,14 +1,27 @@
+use std::fmt::Display;
+
+use crate::span::Span;
+
 /*
 LANGUAGE GRAMMAR
 SOURCE: http://canonical.org/~kragen/memory-models/
-program ::= def*
+program ::= (def | const)*
 def ::= "def" name "(" args ")" block
+const ::= "const" name ":=" exp ";"
 args ::= "" | name "," args
 block ::= "{" statement* "}" 
-statement ::= "return" exp ";" | name ":=" exp ";" | name "[" exp "]" ":=" exp ";"|  exp ";" | nest
-nest ::= "if" (exp) block | "if" (exp) block "else" block | "while" (exp) block
-exp ::= name | num | "[" exp "]" | exp op exp | name "[" exp "]" | name "(" exps ")" | builtin | "(" exp ")" | unop exp
+statement ::= "return" exp ";" | simple ";" | "break" ";" | "continue" ";" | nest
+simple ::= "let" name ":=" exp | name ":=" exp | lvalue ":=" exp | exp
+lvalue ::= name "[" exp "]" | lvalue "[" exp "]"
+nest ::= "if" (exp) block | "if" (exp) block "else" block | "if" (exp) block ("else" "if" (exp) block)+ ("else" block)?
+    | "while" (exp) block
+    | "for" "(" simple ";" exp ";" simple ")" block | "for" name "in" exp block
+exp ::= name | num | string | "true" | "false" | "[" exps "]" | exp op exp | exp "[" exp "]" | name "(" exps ")" | builtin | "(" exp ")" | unop exp
 builtin ::= "sqrt" "(" exps ")" | "len" "(" exps ")" | "round" "(" exps ")" | "input" "(" exps ")"
+    | "str" "(" exps ")" | "num" "(" exps ")" | "substr" "(" exps ")" | "split" "(" exps ")"
+    | "array" "(" exps ")"
+string ::= '"' (char | escape)* '"'
+escape ::= "\n" | "\t" | "\r" | "\0" | "\\" | '\"'
 exps ::= "" | exp "," exps
 unop ::= "!" | "-"
 op ::= logical | comparison | "+" | "*" | "-" | "/" | "%"
@@ -17,6 +30,7 @@ comparison ::= "==" | "<" | ">" | "<=" | ">=" | "!="
 */
 
 #[derive(Debug, PartialEq, EnumDiscriminants, Clone)]
+#[strum_discriminants(derive(PartialOrd, Ord))]
 pub enum Token {
     // file
     Start, // placeholder before first call to next_token()
@@ -35,12 +49,21 @@ pub enum Token {
     Return,       // return
     SColon,       // ;
     Assign,       // :=
+    Let,          // let
+    Const,        // const
 
     // flow
     If,       // if
     Else,     // else
     While,    // while
+    For,      // for
+    In,       // in
+    Break,    // break
+    Continue, // continue
     Num(f64), // numeric value
+    Str(String), // string literal, with escapes replaced
+    True,     // true
+    False,    // false
 
     // unary
     Not,   // !
@@ -69,4 +92,87 @@ pub enum Token {
     Len, // len 
     Round, // round
     Input, // input
+    ToStr, // str
+    ToNum, // num
+    Substr, // substr
+    Split, // split
+    Array, // array
+}
+
+// Display for Token, renders the token as it appears in source
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Num(value) => write!(f, "{}", value),
+            Token::Str(value) => write!(f, "{:?}", value),
+            other => write!(f, "{}", TokenDiscriminants::from(other)),
+        }
+    }
+}
+
+// Display for TokenDiscriminants, renders the kind of token as it appears in source
+impl Display for TokenDiscriminants {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TokenDiscriminants::Start => "start of file",
+            TokenDiscriminants::Eof => "end of file",
+            TokenDiscriminants::Def => "def",
+            TokenDiscriminants::Name => "name",
+            TokenDiscriminants::LParen => "(",
+            TokenDiscriminants::RParen => ")",
+            TokenDiscriminants::Comma => ",",
+            TokenDiscriminants::LCurly => "{",
+            TokenDiscriminants::RCurly => "}",
+            TokenDiscriminants::LBracket => "[",
+            TokenDiscriminants::RBracket => "]",
+            TokenDiscriminants::Return => "return",
+            TokenDiscriminants::SColon => ";",
+            TokenDiscriminants::Assign => ":=",
+            TokenDiscriminants::Let => "let",
+            TokenDiscriminants::Const => "const",
+            TokenDiscriminants::If => "if",
+            TokenDiscriminants::Else => "else",
+            TokenDiscriminants::While => "while",
+            TokenDiscriminants::For => "for",
+            TokenDiscriminants::In => "in",
+            TokenDiscriminants::Break => "break",
+            TokenDiscriminants::Continue => "continue",
+            TokenDiscriminants::Num => "number",
+            TokenDiscriminants::Str => "string",
+            TokenDiscriminants::True => "true",
+            TokenDiscriminants::False => "false",
+            TokenDiscriminants::Not => "!",
+            TokenDiscriminants::Minus => "-",
+            TokenDiscriminants::Plus => "+",
+            TokenDiscriminants::Mul => "*",
+            TokenDiscriminants::Div => "/",
+            TokenDiscriminants::Mod => "%",
+            TokenDiscriminants::Or => "||",
+            TokenDiscriminants::And => "&&",
+            TokenDiscriminants::Equals => "==",
+            TokenDiscriminants::Less => "<",
+            TokenDiscriminants::More => ">",
+            TokenDiscriminants::LessEqual => "<=",
+            TokenDiscriminants::MoreEqual => ">=",
+            TokenDiscriminants::NotEqual => "!=",
+            TokenDiscriminants::Sqrt => "sqrt",
+            TokenDiscriminants::Len => "len",
+            TokenDiscriminants::Round => "round",
+            TokenDiscriminants::Input => "input",
+            TokenDiscriminants::ToStr => "str",
+            TokenDiscriminants::ToNum => "num",
+            TokenDiscriminants::Substr => "substr",
+            TokenDiscriminants::Split => "split",
+            TokenDiscriminants::Array => "array",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+// A Token along with where it was found in the source
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
@@ -0,0 +1,388 @@
+#[cfg(test)]
+mod test;
+
+use std::io::{stdin, stdout, Cursor};
+
+use crate::compiler::bytecode::*;
+use crate::interpreter::errors::{InterpreterError, TraceFrame, Traceback};
+use crate::interpreter::host::{HostFnBody, HostFns};
+use crate::interpreter::input::Input;
+use crate::interpreter::output::Output;
+use crate::interpreter::{ops, Value, MAIN, RECURSION_LIMIT};
+use crate::span::Span;
+
+// Vm runs a compiled Module.
+// it gives the same results and errors as the Interpreter does for the program the Module came from.
+pub struct Vm {
+    module: Module,
+    input: Input,
+    output: Output,
+    // functions registered by the embedder, like Interpreter::register_fn
+    hosts: HostFns,
+    // how many calls may be in progress at once, like InterpreterBuilder::recursion_limit
+    recursion_limit: usize,
+    // conditions must be booleans, like Interpreter::with_strict_conditions
+    strict_conditions: bool,
+    // variables must be declared before they are assigned, like Interpreter::with_strict_declarations
+    strict_declarations: bool,
+}
+
+// Frame is a function call in progress
+struct Frame {
+    func: usize,
+    ip: usize,
+    // where the function's locals start
+    base: usize,
+    // the call expression that made this call, None for the entry function
+    site: Option<usize>,
+}
+
+// What to do after an instruction
+enum Step {
+    Next,
+    Done(Option<Value>),
+}
+
+// Machine is the state of a running Vm
+struct Machine<'a> {
+    module: &'a Module,
+    input: &'a mut Input,
+    output: &'a mut Output,
+    hosts: &'a HostFns,
+    recursion_limit: usize,
+    // whether the entry frame is a call, rather than the consts' init function which the Interpreter doesn't count
+    entry_is_call: bool,
+    strict_conditions: bool,
+    strict_declarations: bool,
+    stack: Vec<Value>,
+    locals: Vec<Option<Value>>,
+    // values of the global consts, None until they are evaluated
+    globals: Vec<Option<Value>>,
+    frames: Vec<Frame>,
+}
+
+impl Vm {
+    pub fn new(module: Module) -> Vm {
+        Vm::new_with_io(module, Input::from(stdin()), Output::from(stdout()))
+    }
+
+    pub fn new_cursored(module: Module, input: Vec<Cursor<String>>) -> Vm {
+        Vm::new_with_io(module, Input::from(input), Output::from(stdout()))
+    }
+
+    // Creates a Vm reading input() from input, and printing to output
+    pub fn new_with_io(module: Module, input: Input, output: Output) -> Vm {
+        Vm {
+            module,
+            input,
+            output,
+            hosts: HostFns::new(),
+            recursion_limit: RECURSION_LIMIT,
+            strict_conditions: false,
+            strict_declarations: false,
+        }
+    }
+
+    // Sets how many calls may be in progress at once, see InterpreterBuilder::recursion_limit
+    pub fn with_recursion_limit(mut self, limit: usize) -> Vm {
+        self.recursion_limit = limit;
+        self
+    }
+
+    // In strict mode, conditions that aren't booleans raise a TypeError, see Interpreter::with_strict_conditions
+    pub fn with_strict_conditions(mut self, strict: bool) -> Vm {
+        self.strict_conditions = strict;
+        self
+    }
+
+    // In strict mode, assigning an undeclared variable raises an UndeclaredVar error, see Interpreter::with_strict_declarations
+    pub fn with_strict_declarations(mut self, strict: bool) -> Vm {
+        self.strict_declarations = strict;
+        self
+    }
+
+    // Exposes body to the Module as the function name, see Interpreter::register_fn
+    pub fn register_fn(&mut self, name: &str, arity: usize, body: HostFnBody) {
+        self.hosts.register(name, arity, body);
+    }
+
+    // Executes this vm's Module, evaluating its consts and then starting from main
+    pub fn execute(self) -> Result<Option<Value>, InterpreterError> {
+        self.execute_with_args(&[])
+    }
+
+    // Executes this vm's Module like execute, returning any error along with the calls it was raised in,
+    // like Interpreter::execute_traced
+    pub fn execute_traced(self) -> Result<Option<Value>, Traceback> {
+        self.run(&[])
+    }
+
+    // Executes this vm's Module like execute, calling main with args
+    pub fn execute_with_args(self, args: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        self.run(args).map_err(|traceback| traceback.error)
+    }
+
+    // Executes this vm's Module like execute_with_args, returning any error along with the calls it was raised in
+    pub fn execute_with_args_traced(self, args: &[Value]) -> Result<Option<Value>, Traceback> {
+        self.run(args)
+    }
+
+    fn run(mut self, args: &[Value]) -> Result<Option<Value>, Traceback> {
+        let mut machine = Machine {
+            module: &self.module,
+            input: &mut self.input,
+            output: &mut self.output,
+            hosts: &self.hosts,
+            recursion_limit: self.recursion_limit,
+            entry_is_call: false,
+            strict_conditions: self.strict_conditions,
+            strict_declarations: self.strict_declarations,
+            stack: Vec::new(),
+            locals: Vec::new(),
+            globals: vec![None; self.module.globals.len()],
+            frames: Vec::new(),
+        };
+        if let Some(init) = self.module.init {
+            machine.enter(init, Vec::new()).map_err(|error| machine.traceback(error))?;
+        }
+
+        let main = match self.module.main {
+            Some(main) => main,
+            None => return Err(untraced(InterpreterError::UnboundFunc(MAIN.to_string(), Span::default()))),
+        };
+        ops::check_arity(self.module.functions[main].arity, args.len()).map_err(untraced)?;
+        machine.enter(main, args.to_vec()).map_err(|error| machine.traceback(error))
+    }
+}
+
+// an error raised outside of any call
+fn untraced(error: InterpreterError) -> Traceback {
+    Traceback {
+        error,
+        frames: Vec::new(),
+    }
+}
+
+impl<'a> Machine<'a> {
+    // runs the function func as the entry function, with args as its first locals
+    fn enter(&mut self, func: usize, args: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+        self.entry_is_call = Some(func) != self.module.init;
+        if self.entry_is_call {
+            self.check_depth(func)?;
+        }
+        self.locals = args.into_iter().map(Some).collect();
+        self.locals.resize(self.module.functions[func].locals.len(), None);
+        self.frames = vec![Frame {
+            func,
+            ip: 0,
+            base: 0,
+            site: None,
+        }];
+        self.run()
+    }
+
+    fn run(&mut self) -> Result<Option<Value>, InterpreterError> {
+        let module = self.module;
+        loop {
+            let frame = self.frames.last_mut().expect("a function is running");
+            let function = &module.functions[frame.func];
+            let ip = frame.ip;
+            frame.ip += 1;
+
+            match self.step(function.code[ip]) {
+                Ok(Step::Next) => {}
+                Ok(Step::Done(value)) => return Ok(value),
+                Err(err) => return Err(err.with_span(function.spans[ip])),
+            }
+        }
+    }
+
+    // Executes a single instruction of the current function
+    fn step(&mut self, instr: Instr) -> Result<Step, InterpreterError> {
+        match instr {
+            Instr::Const(constant) => self.stack.push(self.module.constants[constant].clone()),
+            Instr::Load(slot) => {
+                let frame = self.frame();
+                match &self.locals[frame.base + slot] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => {
+                        let name = &self.module.functions[frame.func].locals[slot];
+                        return Err(InterpreterError::UnboundVar(name.clone(), Span::default()));
+                    }
+                }
+            }
+            Instr::Store(slot) => {
+                let base = self.frame().base;
+                self.locals[base + slot] = Some(self.pop());
+            }
+            Instr::LoadGlobal(global) => match &self.globals[global] {
+                Some(value) => self.stack.push(value.clone()),
+                None => {
+                    let name = &self.module.globals[global];
+                    return Err(InterpreterError::UnboundVar(name.clone(), Span::default()));
+                }
+            },
+            Instr::StoreGlobal(global) => self.globals[global] = Some(self.pop()),
+            Instr::StoreUndeclared(slot) => {
+                let frame = self.frame();
+                if self.strict_declarations {
+                    let name = &self.module.functions[frame.func].locals[slot];
+                    return Err(InterpreterError::UndeclaredVar(name.clone(), Span::default()));
+                }
+                let base = frame.base;
+                self.locals[base + slot] = Some(self.pop());
+            }
+            Instr::Binary(op) => {
+                let rhs = self.pop();
+                let lhs = self.pop();
+                self.stack.push(ops::binary(op, lhs, rhs)?);
+            }
+            Instr::Unary(unop) => {
+                let value = self.pop();
+                self.stack.push(ops::unary(&unop, value, self.strict_conditions)?);
+            }
+            Instr::Jump(to) => self.jump(to),
+            Instr::JumpIfTrue(to) => {
+                if ops::truthy(self.pop(), self.strict_conditions)? {
+                    self.jump(to);
+                }
+            }
+            Instr::JumpIfFalse(to) => {
+                if !ops::truthy(self.pop(), self.strict_conditions)? {
+                    self.jump(to);
+                }
+            }
+            Instr::Iterate(exit) => {
+                let position = self.pop().into_f64()? as usize;
+                let iterable = self.pop();
+                match ops::iterate(&iterable, position)? {
+                    Some(element) => self.stack.push(element),
+                    None => self.jump(exit),
+                }
+            }
+            Instr::Array(len) => {
+                let elements = self.stack.len() - len;
+                let elements: Vec<Value> = self.stack.drain(elements..).collect();
+                self.stack.push(Value::from(elements));
+            }
+            Instr::Index => {
+                let index = self.pop();
+                let array = self.pop();
+                self.stack.push(ops::index(array, index)?);
+            }
+            Instr::SetIndex => {
+                let value = self.pop();
+                let index = self.pop();
+                let array = self.pop();
+                ops::set_index(array, index, value)?;
+            }
+            Instr::Call { func, site } => {
+                self.check_depth(func)?;
+                let function = &self.module.functions[func];
+                // args become the callee's first locals, the rest start unassigned
+                let base = self.locals.len();
+                let args = self.stack.len() - function.arity;
+                self.locals.extend(self.stack.drain(args..).map(Some));
+                self.locals.resize(base + function.locals.len(), None);
+
+                self.frames.push(Frame {
+                    func,
+                    ip: 0,
+                    base,
+                    site: Some(site),
+                });
+            }
+            Instr::CallHost { name, args } => {
+                let name = &self.module.host_names[name];
+                let host = match self.hosts.get(name) {
+                    Some(host) => host,
+                    None => return Err(InterpreterError::UnboundFunc(name.clone(), Span::default())),
+                };
+                let args = self.stack.len() - args;
+                let args: Vec<Value> = self.stack.drain(args..).collect();
+                self.stack.push(host.call(&args)?);
+            }
+            Instr::BuiltIn(func) => {
+                let args = self.stack.len() - func.arity();
+                let args = self.stack.drain(args..).collect();
+                let value = func.call(args, self.input, self.output)?;
+                self.stack.push(value);
+            }
+            Instr::Print => {
+                let value = self.pop();
+                self.output.print(value)?;
+            }
+            Instr::Return => {
+                let value = self.pop();
+                self.leave();
+                if self.frames.is_empty() {
+                    return Ok(Step::Done(Some(value)));
+                }
+                self.stack.push(value);
+            }
+            Instr::ReturnNone => {
+                // only the entry function may return nothing, calls must have a value
+                match self.leave().site {
+                    None => return Ok(Step::Done(None)),
+                    Some(site) => {
+                        let exp = self.module.call_sites[site].clone();
+                        return Err(InterpreterError::ValuelessExpression(exp));
+                    }
+                }
+            }
+            Instr::Fail(failure) => return Err(self.module.failures[failure].to_error()),
+        }
+
+        Ok(Step::Next)
+    }
+
+    // Attaches the calls in progress to error, which was raised in the innermost one.
+    // the consts' init function isn't a call, so it is left out like in the Interpreter
+    fn traceback(&self, error: InterpreterError) -> Traceback {
+        let frames = self
+            .frames
+            .iter()
+            .filter(|frame| Some(frame.func) != self.module.init)
+            .map(|frame| TraceFrame {
+                function: self.module.functions[frame.func].name.clone(),
+                call_site: frame
+                    .site
+                    .map(|site| self.module.call_sites[site].span)
+                    .unwrap_or_default(),
+            })
+            .collect();
+        Traceback { error, frames }
+    }
+
+    // Fails if calling func would have more calls in progress than the recursion limit allows
+    fn check_depth(&self, func: usize) -> Result<(), InterpreterError> {
+        let depth = self.frames.len() + 1 - usize::from(!self.entry_is_call);
+        if depth > self.recursion_limit {
+            return Err(InterpreterError::StackOverflow {
+                depth,
+                function: self.module.functions[func].name.clone(),
+                span: Span::default(),
+            });
+        }
+        Ok(())
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("a function is running")
+    }
+
+    fn jump(&mut self, to: usize) {
+        self.frames.last_mut().expect("a function is running").ip = to;
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("an operand is on the stack")
+    }
+
+    // ends the current call, dropping its locals
+    fn leave(&mut self) -> Frame {
+        let frame = self.frames.pop().expect("a function is running");
+        self.locals.truncate(frame.base);
+        frame
+    }
+}
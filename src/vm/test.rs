@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+
+    use super::super::*;
+    use crate::ast::generate_ast;
+    use crate::compiler::compile;
+    use crate::scanner::Scanner;
+
+    fn run(source: &str) -> Result<Option<Value>, InterpreterError> {
+        let mut scan = Scanner::new(source.to_string()).unwrap();
+        Vm::new(compile(&generate_ast(&mut scan).unwrap())).execute()
+    }
+
+    #[test]
+    fn test_loop_and_calls() {
+        let result = run(
+            "def sum(n) { total := 0; i := 1; while (i <= n) { total := total + i; i := i + 1; } return total; }
+             def main() { return sum(100); }",
+        );
+        assert_eq!(result.unwrap(), Some(Value::from(5050f64)));
+    }
+
+    #[test]
+    fn test_main_without_value() {
+        assert_eq!(run("def main() { a := 1; }").unwrap(), None);
+    }
+
+    #[test]
+    fn test_locals_per_call() {
+        // each call gets its own slots, a callee can't see or change the caller's
+        let result = run(
+            "def f(n) { if (n > 0) { x := f(n - 1); return n + x; } return 0; }
+             def main() { x := 10; y := f(3); return x + y; }",
+        );
+        assert_eq!(result.unwrap(), Some(Value::from(16f64)));
+    }
+
+    #[test]
+    fn test_unassigned_local() {
+        match run("def main() {\n  if (0) { a := 1; }\n  return a;\n}") {
+            Err(InterpreterError::UnboundVar(name, span)) => {
+                assert_eq!(name, "a");
+                assert_eq!((span.line, span.col), (3, 10));
+            }
+            other => panic!("expected UnboundVar, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valueless_call() {
+        match run("def f() { a := 1; }\ndef main() { return 1 + f(); }") {
+            Err(InterpreterError::ValuelessExpression(exp)) => {
+                assert_eq!((exp.span.line, exp.span.col), (2, 25));
+            }
+            other => panic!("expected ValuelessExpression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_main() {
+        match run("def f() { return 1; }") {
+            Err(InterpreterError::UnboundFunc(name, span)) => {
+                assert_eq!(name, "main");
+                assert!(span.is_dummy());
+            }
+            other => panic!("expected UnboundFunc, got {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,89 @@
 use std::io::Cursor;
 
-use smp;
-use smp::interpreter::environment::Value;
+use smp::compiler::compile;
+use smp::interpreter::environment::{Value, ValueDiscriminants};
+use smp::interpreter::errors::{InterpreterError, Traceback};
+use smp::interpreter::host::HostFnBody;
+use smp::interpreter::input::Input;
+use smp::interpreter::output::{Buffer, Output};
+use smp::interpreter::symbols::Program;
+use smp::interpreter::{Interpreter, RECURSION_LIMIT};
+use smp::vm::Vm;
+
+// makes the body of a host function, so each backend can be given its own
+type MakeHostFn = fn() -> HostFnBody;
+
+// how both backends are set up before running, anything not set is left at the default
+#[derive(Default)]
+struct Config {
+    // each input() reads the next cursor popped off the back
+    input: Vec<Cursor<String>>,
+    // main is called with these
+    args: Vec<Value>,
+    recursion_limit: Option<usize>,
+    strict_conditions: bool,
+    strict_declarations: bool,
+    // host functions registered by name and arity
+    hosts: Vec<(&'static str, usize, MakeHostFn)>,
+}
+
+fn parse(source: &str) -> Program {
+    let mut s = smp::scanner::Scanner::new(source.to_string()).unwrap();
+    smp::ast::generate_ast(&mut s).unwrap()
+}
+
+// runs source on both the Interpreter and the vm, which must agree, and returns the result
+fn run(source: &str) -> Result<Option<Value>, InterpreterError> {
+    run_with_input(source, Vec::new())
+}
+
+// like run, with each input() reading the next cursor popped off the back of input
+fn run_with_input(source: &str, input: Vec<Cursor<String>>) -> Result<Option<Value>, InterpreterError> {
+    run_printing(source, input).0
+}
+
+// like run_with_input, also returning everything the program printed, which the vm must print too
+fn run_printing(source: &str, input: Vec<Cursor<String>>) -> (Result<Option<Value>, InterpreterError>, String) {
+    run_configured(source, Config { input, ..Config::default() })
+}
+
+// like run_printing, with both backends set up by config
+fn run_configured(source: &str, config: Config) -> (Result<Option<Value>, InterpreterError>, String) {
+    let (result, printed) = run_traced(source, config);
+    (result.map_err(|traceback| traceback.error), printed)
+}
+
+// like run_configured, returning any error along with the calls it was raised in, which the vm must agree on too
+fn run_traced(source: &str, config: Config) -> (Result<Option<Value>, Traceback>, String) {
+    let program = parse(source);
+
+    let (vm_printed, printed) = (Buffer::new(), Buffer::new());
+    let mut vm = Vm::new_with_io(compile(&program), Input::from(config.input.clone()), Output::from(vm_printed.clone()))
+        .with_recursion_limit(config.recursion_limit.unwrap_or(RECURSION_LIMIT))
+        .with_strict_conditions(config.strict_conditions)
+        .with_strict_declarations(config.strict_declarations);
+    let mut interpreter = Interpreter::builder(program)
+        .input(Input::from(config.input))
+        .output(Output::from(printed.clone()))
+        .recursion_limit(config.recursion_limit.unwrap_or(RECURSION_LIMIT))
+        .strict_conditions(config.strict_conditions)
+        .strict_declarations(config.strict_declarations)
+        .build();
+    for (name, arity, body) in config.hosts {
+        vm.register_fn(name, arity, body());
+        interpreter.register_fn(name, arity, body());
+    }
+
+    let compiled = vm.execute_with_args_traced(&config.args);
+    let interpreted = interpreter.execute_with_args_traced(&config.args);
+    assert_eq!(
+        format!("{:?}", interpreted),
+        format!("{:?}", compiled),
+        "the vm and the Interpreter disagree"
+    );
+    assert_eq!(printed.contents(), vm_printed.contents(), "the vm and the Interpreter print differently");
+    (interpreted, printed.contents())
+}
 
 #[test]
 fn test_simple() {
@@ -12,10 +94,7 @@ fn test_simple() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(1f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(1f64)));
 }
 
 #[test]
@@ -35,11 +114,8 @@ fn test_recursive() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
     assert_eq!(
-        interpreter.execute().unwrap(),
+        run(&program).unwrap(),
         Some(Value::from(3628800f64))
     );
 }
@@ -59,10 +135,7 @@ fn test_multiarg() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(120f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(120f64)));
 }
 
 #[test]
@@ -75,10 +148,7 @@ fn test_nested_parens() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(1f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(1f64)));
 }
 
 #[test]
@@ -87,7 +157,7 @@ fn test_arr() {
         "
 
     def main() {
-        arr := [5];
+        arr := array(5);
         i := 0;
         while (i < 5) {
             arr[i] := i;
@@ -98,11 +168,8 @@ fn test_arr() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
     assert_eq!(
-        interpreter.execute().unwrap(),
+        run(&program).unwrap(),
         Some(Value::from(vec![0f64, 1f64, 2f64, 3f64, 4f64]))
     );
 }
@@ -113,16 +180,13 @@ fn test_reassign() {
         "
 
     def main() {
-        a := [10]; # assign a to an array
+        a := array(10); # assign a to an array
         a := 5; # re-assign a to a num
         return a; # we should get back the num
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(5f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(5f64)));
 }
 
 #[test]
@@ -142,10 +206,7 @@ fn test_multicall() {
     }
     ",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(3f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(3f64)));
 }
 
 #[test]
@@ -162,10 +223,7 @@ fn test_comments() {
     # the program is over
     # comments without newline",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(1f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(1f64)));
 }
 
 #[test]
@@ -182,14 +240,11 @@ fn test_builtin() {
     }
     
     def main() {
-        arr := seq([6]);
+        arr := seq(array(6));
         return round(sqrt(arr[len(arr) - 1]));
     }",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(2f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(2f64)));
 }
 
 #[test]
@@ -217,15 +272,12 @@ fn test_many() {
 
    
     def main() {
-        arr := roundSqrtAll(seqPlusOne([100]));
+        arr := roundSqrtAll(seqPlusOne(array(100)));
         return arr[len(arr) - 1];
         
     }",
     );
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new(program);
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(10f64)));
+    assert_eq!(run(&program).unwrap(), Some(Value::from(10f64)));
 }
 
 #[test]
@@ -239,11 +291,947 @@ fn test_input() {
     ",
     );
 
-    let mut s = smp::scanner::Scanner::new(program).unwrap();
-    let program = smp::ast::generate_ast(&mut s).unwrap();
-    let interpreter = smp::interpreter::Interpreter::new_cursored(
-        program,
-        vec![Cursor::new("5".to_string()), Cursor::new("5".to_string())],
+    let input = vec![Cursor::new("5".to_string()), Cursor::new("5".to_string())];
+    assert_eq!(run_with_input(&program, input).unwrap(), Some(Value::from(10f64)));
+}
+
+#[test]
+fn test_precedence() {
+    let program = String::from(
+        "
+    def main() {
+        a := 2 * 3 + 4; # 10
+        b := 10 - 2 - 3; # 5
+        c := 100 / 10 / 5; # 2
+        return a + b * c - 6 / 2 % 4; # 10 + 10 - 3
+    }
+    ",
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from(17f64)));
+}
+
+#[test]
+fn test_short_circuit() {
+    // each input() pops the next cursor from the back, so the returned
+    // value tells us how many inputs the logical operators consumed
+    let program = String::from(
+        "
+    def reads() {
+        return input();
+    }
+
+    def main() {
+        a := input() && input(); # lhs is 0, rhs is skipped
+        b := reads() || reads(); # lhs is 1, rhs is skipped
+        return input();
+    }
+    ",
+    );
+
+    let input = vec![
+            Cursor::new("4".to_string()),
+            Cursor::new("3".to_string()),
+            Cursor::new("1".to_string()),
+            Cursor::new("0".to_string()),
+        ];
+    assert_eq!(run_with_input(&program, input).unwrap(), Some(Value::from(3f64)));
+}
+
+#[test]
+fn test_operands_evaluated_once() {
+    let program = String::from(
+        "
+    def main() {
+        a := input() < input(); # 1 < 2
+        b := input() && input(); # 3 && 4, both read exactly once
+        if (a && b) {
+            return input(); # 5
+        }
+        return 0;
+    }
+    ",
+    );
+
+    let input = vec![
+            Cursor::new("5".to_string()),
+            Cursor::new("4".to_string()),
+            Cursor::new("3".to_string()),
+            Cursor::new("2".to_string()),
+            Cursor::new("1".to_string()),
+        ];
+    assert_eq!(run_with_input(&program, input).unwrap(), Some(Value::from(5f64)));
+}
+
+#[test]
+fn test_error_span() {
+    let program = String::from(
+        "
+    def main() {
+        a := 1;
+        return a + (2 * b);
+    }
+    ",
+    );
+    match run(&program) {
+        Err(InterpreterError::UnboundVar(name, span)) => {
+            assert_eq!(name, "b");
+            assert_eq!((span.line, span.col), (4, 25));
+        }
+        other => panic!("expected UnboundVar, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_backends_agree_on_errors() {
+    // run asserts both backends raise the same error, at the same place
+    let programs = [
+        "def main() { a := array(3); return a + 1; }",
+        "def main() { return 1 / (2 - 2); }",
+        "def f(a) { return a; } def main() { return f(1, 2); }",
+        "def main() { return g(1); }",
+        "def f() { a := 1; } def main() { return f() * 2; }",
+        "def main() { if (array(1)) { return 1; } return 0; }",
+        "def main() { a[0] := 1; return a; }",
+        "def main() { return sqrt(1, 2); }",
+        "def f() { return 1; }",
+    ];
+    for program in programs.iter() {
+        assert!(run(program).is_err(), "{} should fail", program);
+    }
+}
+
+#[test]
+fn test_array_aliasing() {
+    // assigning an array aliases it, both names see every change
+    let program = String::from(
+        "
+    def main() {
+        a := array(3);
+        b := a;
+        b[0] := 7;
+        a[1] := 8;
+        return a[0] + b[1];
+    }
+    ",
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from(15f64)));
+}
+
+#[test]
+fn test_array_mutated_by_callee() {
+    // arrays are passed by reference, so callees can fill them in place
+    let program = String::from(
+        "
+    def fill(arr, value) {
+        i := 0;
+        while (i < len(arr)) {
+            arr[i] := value;
+            i := i + 1;
+        }
+        return 0;
+    }
+
+    def main() {
+        arr := array(3);
+        ignored := fill(arr, 2);
+        return arr;
+    }
+    ",
+    );
+    assert_eq!(
+        run(&program).unwrap(),
+        Some(Value::from(vec![2f64, 2f64, 2f64]))
+    );
+}
+
+#[test]
+fn test_array_reassign_breaks_alias() {
+    // rebinding a name to a new array leaves other aliases of the old array alone
+    let program = String::from(
+        "
+    def main() {
+        a := array(2);
+        b := a;
+        b := array(2);
+        b[0] := 1;
+        return a;
+    }
+    ",
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from(vec![0f64, 0f64])));
+}
+
+#[test]
+fn test_index_out_of_bounds() {
+    let program = String::from(
+        "
+    def main() {
+        a := array(3);
+        return a[1 + 2];
+    }
+    ",
+    );
+    match run(&program) {
+        Err(InterpreterError::IndexOutOfBounds { index, len, span }) => {
+            assert_eq!((index, len), (3f64, 3));
+            assert_eq!((span.line, span.col), (4, 16));
+        }
+        other => panic!("expected IndexOutOfBounds, got {:?}", other),
+    }
+
+    let program = String::from(
+        "
+    def main() {
+        a := array(3);
+        a[0 - 1] := 1;
+        return a;
+    }
+    ",
+    );
+    match run(&program) {
+        Err(InterpreterError::IndexOutOfBounds { index, len, span }) => {
+            assert_eq!((index, len), (-1f64, 3));
+            assert_eq!((span.line, span.col), (4, 9));
+        }
+        other => panic!("expected IndexOutOfBounds, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_non_integer_index() {
+    for index in ["1.5", "sqrt(0 - 1)"].iter() {
+        let program = format!("def main() {{ a := array(3); return a[{}]; }}", index);
+        match run(&program) {
+            Err(InterpreterError::NonIntegerIndex { .. }) => {}
+            other => panic!("expected NonIntegerIndex for {}, got {:?}", index, other),
+        }
+    }
+
+    // indices within epsilon of a whole number are that number
+    let program = "def main() { a := array(3); a[1] := 5; return a[0.1 * 3 * 10 - 2]; }";
+    assert_eq!(run(program).unwrap(), Some(Value::from(5f64)));
+}
+
+#[test]
+fn test_invalid_array_size() {
+    for size in ["0 - 1", "2.5", "sqrt(0 - 1)", "1 / 0.000001 * 1000000000000000000000"].iter() {
+        let program = format!("def main() {{ return array({}); }}", size);
+        match run(&program) {
+            Err(InterpreterError::InvalidArraySize { .. }) => {}
+            other => panic!("expected InvalidArraySize for {}, got {:?}", size, other),
+        }
+    }
+    assert_eq!(run("def main() { return array(0); }").unwrap(), Some(Value::from(Vec::<f64>::new())));
+}
+
+#[test]
+fn test_array_literals() {
+    let program = String::from(
+        r#"
+    def main() {
+        a := [1, "two", 1 < 2, []];
+        a[3] := [len(a)];
+        return str(a) + str(len(array(2))) + str([3, 4][1]);
+    }
+    "#,
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from("[1.0, \"two\", true, [4.0]]24")));
+}
+
+#[test]
+fn test_nested_arrays() {
+    let program = String::from(
+        "
+    def identity(n) {
+        m := array(n);
+        i := 0;
+        while (i < n) {
+            m[i] := array(n);
+            m[i][i] := 1;
+            i := i + 1;
+        }
+        return m;
+    }
+
+    def main() {
+        m := identity(3);
+        row := m[1];
+        # rows are shared, so this updates m too
+        row[2] := 5;
+        m[2][0] := m[1][1] + m[1][2];
+        return m;
+    }
+    ",
+    );
+    let row = |elements: Vec<f64>| Value::from(elements);
+    assert_eq!(
+        run(&program).unwrap(),
+        Some(Value::from(vec![
+            row(vec![1.0, 0.0, 0.0]),
+            row(vec![0.0, 1.0, 5.0]),
+            row(vec![6.0, 0.0, 1.0]),
+        ]))
+    );
+
+    match run("def main() { m := [[1, 1], 2]; m[1][0] := 3; return m; }") {
+        Err(InterpreterError::TypeError { .. }) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match run("def main() { m := [[1, 1]]; m[1][0] := 3; return m; }") {
+        Err(InterpreterError::IndexOutOfBounds { index, len, .. }) => assert_eq!((index, len), (1f64, 1)),
+        other => panic!("expected IndexOutOfBounds, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_else_if() {
+    let program = String::from(
+        r#"
+    def grade(score) {
+        if (score >= 90) {
+            return "a";
+        } else if (score >= 80) {
+            return "b";
+        } else if (score >= 70) {
+            return "c";
+        } else {
+            return "f";
+        }
+    }
+
+    def main() {
+        grades := "";
+        for score in [95, 85, 75, 10] {
+            grades := grades + grade(score);
+        }
+        # without a final else, nothing runs when no branch matches
+        if (false) {
+            grades := "";
+        } else if (false) {
+            grades := "";
+        }
+        return grades;
+    }
+    "#,
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from("abcf")));
+}
+
+#[test]
+fn test_for_loops() {
+    let program = String::from(
+        r#"
+    def main() {
+        total := 0;
+        for (i := 0; i < 10; i := i + 1) {
+            if (i % 2 == 0) {
+                continue;
+            }
+            if (i > 7) {
+                break;
+            }
+            total := total + i;
+        }
+
+        letters := "";
+        for c in "abc" {
+            letters := c + letters;
+        }
+
+        m := [[1, 2], [3, 4]];
+        sum := 0;
+        for row in m {
+            for x in row {
+                if (x == 2) {
+                    continue;
+                }
+                sum := sum + x;
+            }
+        }
+        return str(total) + letters + str(sum);
+    }
+    "#,
+    );
+    // total is 1 + 3 + 5 + 7
+    assert_eq!(run(&program).unwrap(), Some(Value::from("16cba8")));
+}
+
+#[test]
+fn test_return_from_loops() {
+    let program = String::from(
+        "
+    def find(arr, target) {
+        for (i := 0; i < len(arr); i := i + 1) {
+            while (true) {
+                if (arr[i] == target) {
+                    return i;
+                }
+                break;
+            }
+        }
+        return 0 - 1;
+    }
+
+    def main() {
+        return find([5, 6, 7], 7) * 10 + find([], 1);
+    }
+    ",
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from(19f64)));
+
+    match run("def main() { for x in 5 { return x; } return 0; }") {
+        Err(InterpreterError::TypeError { found_type, expected_type, .. }) => {
+            assert_eq!((found_type, expected_type), (ValueDiscriminants::Num, ValueDiscriminants::Array))
+        }
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_block_scopes() {
+    let program = String::from(
+        "
+    def main() {
+        x := 1;
+        total := 0;
+        if (true) {
+            # assigns the outer x
+            x := 2;
+            # declares a new x for this block only
+            let x := 10;
+            x := x + 1;
+            total := total + x;
+            y := 5;
+        }
+        for (let i := 0; i < 3; i := i + 1) {
+            let x := i;
+            total := total + x;
+        }
+        for x in [60, 40] {
+            total := total + x;
+        }
+        return total * 10 + x;
+    }
+    ",
     );
-    assert_eq!(interpreter.execute().unwrap(), Some(Value::from(10f64)));
+    // 11 + (0 + 1 + 2) + 100, and the outer x was only changed to 2
+    assert_eq!(run(&program).unwrap(), Some(Value::from(1142f64)));
+
+    // variables declared in a block go away when it ends
+    for source in [
+        "def main() { if (true) { y := 1; } return y; }",
+        "def main() { for (i := 0; i < 1; i := i + 1) { } return i; }",
+        "def main() { for x in [1, 2] { } return x; }",
+    ]
+    .iter()
+    {
+        match run(source) {
+            Err(InterpreterError::UnboundVar(name, _)) => assert!(name == "y" || name == "i" || name == "x"),
+            other => panic!("expected UnboundVar for {}, got {:?}", source, other),
+        }
+    }
+}
+
+#[test]
+fn test_strict_declarations() {
+    let run_strict = |source: &str| {
+        run_configured(source, Config { strict_declarations: true, ..Config::default() }).0
+    };
+
+    let program = "def main() { let count := 1; if (true) { count := count + 1; } return count; }";
+    assert_eq!(run_strict(program).unwrap(), Some(Value::from(2f64)));
+
+    // a typo no longer creates a new variable
+    match run_strict("def main() {
+  let count := 1;
+  cuont := count + 1;
+  return count;
+}") {
+        Err(InterpreterError::UndeclaredVar(name, span)) => {
+            assert_eq!(name, "cuont");
+            assert_eq!((span.line, span.col), (3, 3));
+        }
+        other => panic!("expected UndeclaredVar, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_consts() {
+    let program = "
+const SIZE := 3;
+const CELLS := SIZE * SIZE;
+
+def area() {
+    return CELLS;
+}
+
+def main() {
+    return area() + SIZE;
+}";
+    assert_eq!(run(program).unwrap(), Some(Value::from(12f64)));
+
+    // consts are evaluated once, in order, before main
+    let program = "
+const A := first();
+
+def first() {
+    \"init\";
+    return 1;
+}
+
+def main() {
+    \"main\";
+    return A + A;
+}";
+    assert_eq!(run(program).unwrap(), Some(Value::from(2f64)));
+
+    // a const can't use one declared after it
+    match run("const A := B; const B := 1; def main() { return A; }") {
+        Err(InterpreterError::UnboundVar(name, _)) => assert_eq!(name, "B"),
+        other => panic!("expected UnboundVar, got {:?}", other),
+    }
+
+    // let declares a variable that shadows the const
+    let program = "const N := 1; def main() { let N := 2; N := N + 1; return N; }";
+    assert_eq!(run(program).unwrap(), Some(Value::from(3f64)));
+}
+
+#[test]
+fn test_const_assign() {
+    match run("const LIMIT := 10;
+def main() {
+  LIMIT := 11;
+  return LIMIT;
+}") {
+        Err(InterpreterError::ConstAssign(name, span)) => {
+            assert_eq!(name, "LIMIT");
+            assert_eq!((span.line, span.col), (3, 3));
+        }
+        other => panic!("expected ConstAssign, got {:?}", other),
+    }
+
+    // so is assigning an element of an array held by a const, however deeply nested
+    for (assign, col) in [("C[0] := 5;", 3), ("C[1][0] := 5;", 3), ("if (true) { C[0] := 5; }", 15)] {
+        match run(&format!("const C := [1, [2, 3]];\ndef main() {{\n  {}\n  return C[0];\n}}", assign)) {
+            Err(InterpreterError::ConstAssign(name, span)) => {
+                assert_eq!(name, "C");
+                assert_eq!((span.line, span.col), (3, col));
+            }
+            other => panic!("expected ConstAssign, got {:?}", other),
+        }
+    }
+    // but constness is shallow, an alias of the array or a local shadowing the const can change it
+    let program = "const C := [1, 2];
+def main() {
+  a := C;
+  a[0] := 5;
+  let C := [3, 4];
+  C[0] := 6;
+  return a[0] + C[0];
+}";
+    assert_eq!(run(program).unwrap(), Some(Value::from(11f64)));
+
+    // declaring the same const twice is assigning to it
+    match run("const A := 1;\nconst A := 2;\ndef main() { return A; }") {
+        Err(InterpreterError::ConstAssign(name, span)) => {
+            assert_eq!(name, "A");
+            assert_eq!(span.line, 2);
+        }
+        other => panic!("expected ConstAssign, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_host_functions() {
+    fn hypot() -> HostFnBody {
+        Box::new(|args| {
+            let (x, y) = (args[0].clone().into_f64()?, args[1].clone().into_f64()?);
+            Ok(Value::from((x * x + y * y).sqrt()))
+        })
+    }
+    let run_hosted = |source: &str| {
+        run_configured(source, Config { hosts: vec![("hypot", 2, hypot)], ..Config::default() }).0
+    };
+
+    let program = "def main() { return hypot(3, 2 + 2) + 1; }";
+    assert_eq!(run_hosted(program).unwrap(), Some(Value::from(6f64)));
+
+    // a def with the same name takes precedence
+    let program = "def hypot(a, b) { return a + b; } def main() { return hypot(3, 4); }";
+    assert_eq!(run_hosted(program).unwrap(), Some(Value::from(7f64)));
+
+    // arity is checked like a def's
+    match run_hosted("def main() {\n  return hypot(3);\n}") {
+        Err(InterpreterError::ArgMismatch { got, expected, span }) => {
+            assert_eq!((got, expected), (1, 2));
+            assert_eq!((span.line, span.col), (2, 10));
+        }
+        other => panic!("expected ArgMismatch, got {:?}", other),
+    }
+
+    // errors from the host function are located at the call
+    match run_hosted("def main() {\n  return hypot(3, \"4\");\n}") {
+        Err(InterpreterError::TypeError { span, .. }) => assert_eq!((span.line, span.col), (2, 10)),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+
+    // without a registered function the name is still unbound
+    match run("def main() { return hypot(3, 4); }") {
+        Err(InterpreterError::UnboundFunc(name, _)) => assert_eq!(name, "hypot"),
+        other => panic!("expected UnboundFunc, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_output() {
+    let program = "def main() { \"hi\"; i := 0; while (i < 2) { i; i := i + 1; } return input(); }";
+    let (result, printed) = run_printing(program, vec![Cursor::new(String::from("7"))]);
+    assert_eq!(result.unwrap(), Some(Value::from(7f64)));
+    assert_eq!(printed, "hi\n0\n1\n> ");
+
+    // the input() prompt can be changed or turned off
+    let run_prompted = |prompt: Option<&str>| {
+        let printed = Buffer::new();
+        let output = Output::from(printed.clone()).with_prompt(prompt);
+        let input = Input::from(vec![Cursor::new(String::from("1"))]);
+        Interpreter::new_with_io(parse("def main() { return input(); }"), input, output).execute().unwrap();
+        printed.contents()
+    };
+    assert_eq!(run_prompted(Some("number? ")), "number? ");
+    assert_eq!(run_prompted(None), "");
+
+    // a failed write is an output error, not an input one
+    struct Closed;
+    impl std::io::Write for Closed {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let closed = || Output::from(Box::new(Closed) as Box<dyn std::io::Write>);
+    let program = parse("def main() {\n    \"hi\";\n    return 1;\n}");
+    let results = [
+        Interpreter::new_with_io(program.clone(), Input::from(Vec::new()), closed()).execute(),
+        Vm::new_with_io(compile(&program), Input::from(Vec::new()), closed()).execute(),
+    ];
+    for result in results {
+        match result {
+            Err(InterpreterError::OutputError(_, span)) => assert_eq!((span.line, span.col), (2, 5)),
+            other => panic!("expected OutputError, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_builder() {
+    let source = "def start(a, b) { twice(a); return twice(a) + b; }";
+
+    let printed = Buffer::new();
+    let result = Interpreter::builder(parse(source))
+        .output(Output::from(printed.clone()))
+        .entry("start")
+        .args(vec![Value::from(2f64), Value::from(1f64)])
+        .register_fn("twice", 1, Box::new(|args| Ok(Value::from(args[0].clone().into_f64()? * 2.0))))
+        .strict_declarations(true)
+        .build()
+        .execute();
+    assert_eq!(result.unwrap(), Some(Value::from(5f64)));
+    assert_eq!(printed.contents(), "4\n");
+
+    // the entry must exist and take as many args as it is given
+    match Interpreter::builder(parse(source)).entry("begin").build().execute() {
+        Err(InterpreterError::UnboundFunc(name, _)) => assert_eq!(name, "begin"),
+        other => panic!("expected UnboundFunc, got {:?}", other),
+    }
+    match Interpreter::builder(parse(source)).entry("start").args(vec![Value::from(1f64)]).build().execute() {
+        Err(InterpreterError::ArgMismatch { got, expected, .. }) => assert_eq!((got, expected), (1, 2)),
+        other => panic!("expected ArgMismatch, got {:?}", other),
+    }
+
+    let strict = Interpreter::builder(parse("def main() { x := 1; return x; }")).strict_declarations(true);
+    assert!(matches!(strict.build().execute(), Err(InterpreterError::UndeclaredVar(..))));
+}
+
+#[test]
+fn test_execute_with_args() {
+    let run_with_args = |source: &str, args: &[Value]| {
+        run_configured(source, Config { args: args.to_vec(), ..Config::default() }).0
+    };
+
+    let program = "def main(a, b) { c := a * b; return c; }";
+    let result = run_with_args(program, &[Value::from(3f64), Value::from(4.5f64)]);
+    assert_eq!(result.unwrap(), Some(Value::from(13.5f64)));
+
+    let program = "def main(args) { return len(args); }";
+    let args = Value::from(vec![Value::from("x"), Value::from(1f64)]);
+    assert_eq!(run_with_args(program, &[args]).unwrap(), Some(Value::from(2f64)));
+
+    match run_with_args("def main() { return 1; }", &[Value::from(1f64)]) {
+        Err(InterpreterError::ArgMismatch { got, expected, .. }) => assert_eq!((got, expected), (1, 0)),
+        other => panic!("expected ArgMismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_command_line_args() {
+    // runs the smp binary on source with args, returning what it printed
+    let smp = |source: &str, args: &[&str]| {
+        let path = std::env::temp_dir().join(format!("smp_args_{}.smp", std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_smp"))
+            .arg(&path)
+            .args(args)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // a single parameter gets every arg in an array, even when there is only one
+    let program = "def main(args) { return len(args); }";
+    assert_eq!(smp(program, &[]), "Main: 0\n");
+    assert_eq!(smp(program, &["3"]), "Main: 1\n");
+    assert_eq!(smp(program, &["xyz"]), "Main: 1\n");
+    assert_eq!(smp(program, &["3", "x"]), "Main: 2\n");
+    assert_eq!(smp("def main(args) { return args[0] + 1; }", &["3"]), "Main: 4\n");
+
+    assert_eq!(smp("def main(a, b) { return a * b; }", &["3", "4.5"]), "Main: 13.5\n");
+}
+
+#[test]
+fn test_stack_overflow() {
+    // a test thread runs out of stack before the default limit, which stops the Interpreter first.
+    // the vm keeps its calls on the heap, so it always goes up to the limit
+    let runaway = parse("def f(n) {\n  return f(n);\n}\ndef main() { return f(1); }");
+    match Interpreter::new(runaway.clone()).execute() {
+        Err(InterpreterError::StackOverflow { depth, function, span }) => {
+            assert!(depth <= 1001);
+            assert_eq!(function, "f");
+            assert_eq!((span.line, span.col), (2, 10));
+        }
+        other => panic!("expected StackOverflow, got {:?}", other),
+    }
+    match Vm::new(compile(&runaway)).execute() {
+        Err(InterpreterError::StackOverflow { depth, .. }) => assert_eq!(depth, 1001),
+        other => panic!("expected StackOverflow, got {:?}", other),
+    }
+
+    // calls nested in blocks take more stack
+    let nested = format!(
+        "def f(n) {{ {} return f(n + 1); {} return 0; }} def main() {{ return f(1); }}",
+        "if (true) { ".repeat(8),
+        "} ".repeat(8)
+    );
+    match Interpreter::new(parse(&nested)).execute() {
+        Err(InterpreterError::StackOverflow { depth, .. }) => assert!(depth <= 1001),
+        other => panic!("expected StackOverflow, got {:?}", other),
+    }
+
+    let run_limited = |source: &str, limit: usize| {
+        run_configured(source, Config { recursion_limit: Some(limit), ..Config::default() }).0
+    };
+    let fact = "def fact(n) { if (n <= 1) { return 1; } return n * fact(n - 1); }";
+    let program = format!("{} def main() {{ return fact(5); }}", fact);
+    assert_eq!(run_limited(&program, 6).unwrap(), Some(Value::from(120f64)));
+    match run_limited(&program, 5) {
+        Err(InterpreterError::StackOverflow { depth, function, .. }) => assert_eq!((depth, function.as_str()), (6, "fact")),
+        other => panic!("expected StackOverflow, got {:?}", other),
+    }
+
+    // calls made by consts start from the same depth as main
+    let program = format!("const A := fact(5); {} def main() {{ return A; }}", fact);
+    assert_eq!(run_limited(&program, 5).unwrap(), Some(Value::from(120f64)));
+    assert!(matches!(run_limited(&program, 4), Err(InterpreterError::StackOverflow { .. })));
+}
+
+#[test]
+fn test_step_budget() {
+    // the return statement, the sum and both of its operands
+    let (value, steps) = Interpreter::new(parse("def main() { return 1 + 2; }")).execute_counting_steps().unwrap();
+    assert_eq!((value, steps), (Some(Value::from(3f64)), 4));
+
+    let endless = "def main() {\n  while (1) { }\n}";
+    match Interpreter::builder(parse(endless)).step_budget(1000).build().execute() {
+        Err(InterpreterError::BudgetExhausted { budget, span }) => {
+            assert_eq!(budget, 1000);
+            assert_eq!((span.line, span.col), (2, 10));
+        }
+        other => panic!("expected BudgetExhausted, got {:?}", other),
+    }
+
+    // a budget that is big enough doesn't change anything
+    let program = parse("def main() { i := 0; while (i < 3) { i := i + 1; } return i; }");
+    let result = Interpreter::builder(program).step_budget(1000).build().execute_counting_steps();
+    assert_eq!(result.unwrap(), (Some(Value::from(3f64)), 29));
+}
+
+#[test]
+fn test_deadline() {
+    let soon = std::time::Instant::now() + std::time::Duration::from_millis(20);
+
+    match Interpreter::builder(parse("def main() {\n  while (1) { }\n}")).deadline(soon).build().execute() {
+        Err(InterpreterError::DeadlineExceeded(span)) => assert_eq!((span.line, span.col), (2, 3)),
+        other => panic!("expected DeadlineExceeded, got {:?}", other),
+    }
+
+    // calls are checked too, so recursion can't run past it
+    let program = parse("def f(n) { if (n > 0) { return f(n - 1) + f(n - 1); } return 0; } def main() { return f(50); }");
+    let result = Interpreter::builder(program).deadline(soon).build().execute();
+    assert!(matches!(result, Err(InterpreterError::DeadlineExceeded(_))));
+}
+
+#[test]
+fn test_traceback() {
+    let traced = |source: &str| run_traced(source, Config::default()).0.unwrap_err();
+
+    let source = "def g(x) {\n  return x / 0;\n}\ndef f(x) {\n  y := g(x);\n  return y;\n}\ndef main() {\n  return f(1);\n}";
+    let traceback = traced(source);
+    let frames: Vec<&str> = traceback.frames.iter().map(|frame| frame.function.as_str()).collect();
+    assert_eq!(frames, ["main", "f", "g"]);
+    assert!(traceback.frames[0].call_site.is_dummy());
+    assert_eq!((traceback.frames[2].call_site.line, traceback.frames[2].call_site.col), (5, 8));
+    assert_eq!(
+        traceback.to_string(),
+        "Traceback (most recent call last):\n  \
+         line 9, col 10, in main\n  \
+         line 5, col 8, in f\n  \
+         line 2, col 10, in g\n\
+         error: division by zero"
+    );
+    assert!(traceback
+        .render("prog.smp", source)
+        .starts_with("Traceback (most recent call last):\n  File \"prog.smp\", line 9, col 10, in main\n"));
+
+    // errors outside of any call, and calls that were never entered, have no frames
+    assert_eq!(traced("def f(a) { return a; } def main() { return f(); }").frames.len(), 1);
+    assert!(traced("const A := 1 / 0; def main() { return A; }").frames.is_empty());
+
+    // runaway recursion repeats the same frame, which is only shown a few times
+    let runaway = parse("def f(n) {\n  return f(n);\n}\ndef main() {\n  return f(1);\n}");
+    let traceback = Interpreter::builder(runaway).recursion_limit(10).build().execute_traced().unwrap_err();
+    assert_eq!(traceback.frames.len(), 10);
+    assert_eq!(
+        traceback.to_string(),
+        "Traceback (most recent call last):\n  \
+         line 5, col 10, in main\n  \
+         line 2, col 10, in f\n  \
+         line 2, col 10, in f\n  \
+         line 2, col 10, in f\n  \
+         [Previous line repeated 6 more times]\n\
+         error: stack overflow calling `f`, 11 calls deep"
+    );
+
+    // so do the traced entry points given args, and the builder's
+    let source = "def f(x) {\n  return x / 0;\n}\ndef start(x) {\n  return f(x);\n}";
+    let config = Config { args: vec![Value::from(1f64)], ..Config::default() };
+    let traceback = run_traced(&source.replace("start", "main"), config).0.unwrap_err();
+    assert_eq!(traceback.frames.len(), 2);
+    let built = Interpreter::builder(parse(source)).entry("start").args(vec![Value::from(1f64)]).build();
+    let traceback = built.execute_traced().unwrap_err();
+    let frames: Vec<&str> = traceback.frames.iter().map(|frame| frame.function.as_str()).collect();
+    assert_eq!(frames, ["start", "f"]);
+}
+
+#[test]
+fn test_strings() {
+    let program = String::from(
+        r#"
+    def greet(name) {
+        return "hello, " + name + "!";
+    }
+
+    def main() {
+        s := greet("smp");
+        if (s == "hello, smp!" && s != "hello") {
+            return s[7] + str(len(s));
+        }
+        return "wrong";
+    }
+    "#,
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from("s11")));
+}
+
+#[test]
+fn test_string_builtins() {
+    let program = String::from(
+        r#"
+    def main() {
+        parts := split("3,4.5,x", ",");
+        total := num(parts[0]) + num(" 4.5 ");
+        return str(total) + "|" + substr("hello", 1, 3) + "|" + parts[2] + str(len(split("ab", "")));
+    }
+    "#,
+    );
+    assert_eq!(run(&program).unwrap(), Some(Value::from("7.5|ell|x2")));
+
+    // str shows arrays the way they are printed
+    let program = "def main() { a := array(2); a[1] := \"x\"; return str(a); }";
+    assert_eq!(run(program).unwrap(), Some(Value::from("[0.0, \"x\"]")));
+}
+
+#[test]
+fn test_string_errors() {
+    match run("def main() { return \"a\" + 1; }") {
+        Err(InterpreterError::TypeError { .. }) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match run("def main() { return substr(\"abc\", 2, 2); }") {
+        Err(InterpreterError::IndexOutOfBounds { index, len, .. }) => assert_eq!((index, len), (4f64, 3)),
+        other => panic!("expected IndexOutOfBounds, got {:?}", other),
+    }
+    match run("def main() { s := \"abc\"; s[0] := \"x\"; return s; }") {
+        Err(InterpreterError::TypeError { .. }) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match run("def main() { return num(\"abc\"); }") {
+        Err(InterpreterError::InvalidInput(..)) => {}
+        other => panic!("expected InvalidInput, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_booleans() {
+    let program = String::from(
+        "
+    def main() {
+        flag := 2 > 1;
+        if (flag == true && !false) {
+            a := array(3);
+            a[0] := flag;
+            a[1] := 1 == 2;
+            a[2] := true || x;
+            return a;
+        }
+        return 0;
+    }
+    ",
+    );
+    assert_eq!(
+        run(&program).unwrap(),
+        Some(Value::from(vec![Value::from(true), Value::from(false), Value::from(true)]))
+    );
+
+    // booleans are not numbers
+    match run("def main() { return true + 1; }") {
+        Err(InterpreterError::TypeError { .. }) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+
+    // without strict conditions, numbers still have truthiness
+    assert_eq!(run("def main() { if (2) { return 1; } return 0; }").unwrap(), Some(Value::from(1f64)));
+}
+
+#[test]
+fn test_strict_conditions() {
+    let program = "def main() {\n  if (1) { return 1; }\n  return 0;\n}";
+    match run_configured(program, Config { strict_conditions: true, ..Config::default() }).0 {
+        Err(InterpreterError::TypeError { found_type, expected_type, span }) => {
+            assert_eq!((found_type, expected_type), (ValueDiscriminants::Num, ValueDiscriminants::Bool));
+            assert_eq!((span.line, span.col), (2, 3));
+        }
+        other => panic!("expected TypeError, got {:?}", other),
+    }
 }